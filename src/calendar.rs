@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use crate::date::Date;
+
+/// Types of days that the period classification distinguishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayType {
+    Workday,
+    /// Saturdays and Sundays which aren't bank holidays.
+    Weekend,
+    BankHoliday,
+}
+
+impl DayType {
+    pub const ALL: [DayType; 3] = [DayType::Workday, DayType::Weekend, DayType::BankHoliday];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DayType::Workday => "Workday",
+            DayType::Weekend => "Weekend",
+            DayType::BankHoliday => "Bank holiday",
+        }
+    }
+}
+
+/// Answers which type each day is.
+///
+/// The effective bank holidays are resolved once per run, so that classifying each reading is a
+/// cheap lookup.
+#[derive(Debug, Default)]
+pub struct Calendar {
+    holidays: HashSet<Date>,
+}
+
+impl Calendar {
+    pub fn new(bank_holidays: impl IntoIterator<Item = Date>) -> Self {
+        Calendar {
+            holidays: bank_holidays.into_iter().collect(),
+        }
+    }
+
+    /// Returns the type of `date`; bank holidays take precedence over weekends.
+    pub fn day_type(&self, date: Date) -> DayType {
+        if self.is_holiday(date) {
+            DayType::BankHoliday
+        } else if date.weekday() >= 6 {
+            DayType::Weekend
+        } else {
+            DayType::Workday
+        }
+    }
+
+    pub fn is_holiday(&self, date: Date) -> bool {
+        self.holidays.contains(&date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holidays() {
+        let date = |y, m, d| Date::new(y, m, d).unwrap();
+        let calendar = Calendar::new([date(2023, 1, 6), date(2024, 1, 6), date(2023, 1, 6)]);
+
+        assert!(calendar.is_holiday(date(2023, 1, 6)));
+        assert!(calendar.is_holiday(date(2024, 1, 6)));
+        assert!(!calendar.is_holiday(date(2023, 1, 7)));
+        assert!(!calendar.is_holiday(date(2025, 1, 6)));
+        assert!(!Calendar::default().is_holiday(date(2023, 1, 6)));
+    }
+
+    #[test]
+    fn day_types() {
+        let date = |y, m, d| Date::new(y, m, d).unwrap();
+        // 2023-01-06 is a Friday and 2023-01-07 a Saturday.
+        let calendar = Calendar::new([date(2023, 1, 6), date(2023, 1, 7)]);
+
+        assert_eq!(calendar.day_type(date(2023, 1, 5)), DayType::Workday);
+        assert_eq!(calendar.day_type(date(2023, 1, 6)), DayType::BankHoliday);
+        assert_eq!(calendar.day_type(date(2023, 1, 7)), DayType::BankHoliday);
+        assert_eq!(calendar.day_type(date(2023, 1, 8)), DayType::Weekend);
+        assert_eq!(calendar.day_type(date(2023, 1, 9)), DayType::Workday);
+    }
+}
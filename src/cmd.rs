@@ -0,0 +1,684 @@
+use std::path::{Path, PathBuf};
+
+use crate::calendar::{Calendar, DayType};
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::error::Error;
+use crate::kpi::Kpis;
+use crate::period::{DayIndex, Period};
+use crate::quality::Quality;
+use crate::reader::{self, Reading};
+use crate::report::{Daily, Field, Filter, SortKey};
+use crate::utils;
+
+pub const USAGE: &str = "\
+Sum up the consumption of the CSV files exported by the electricity distributors per time period.
+
+Usage: electricity-meter [OPTIONS] <CSV_FILEPATH>...
+       electricity-meter [OPTIONS] --print-effective-windows
+
+Arguments:
+  <CSV_FILEPATH>...  Consumption CSV files downloaded from the distributor (see --input-format);
+                    use - to read from the standard input. The file names can contain * and ?
+                    wildcards. Several files are merged chronologically as a single series,
+                    ignoring the readings of hours already present in a previous file
+
+Options:
+  -c, --base-counters <p1=N,p2=N,p3=N>
+          Meter counters of each period at the beginning of the CSV data, in kWh; they are the
+          base to add up the CSV readings [default: p1=0,p2=0,p3=0]
+      --p1 <WINDOWS>
+          Windows of the P1 period; their limits are hours or H:MM times, the start is
+          included and the end excluded [default: 10-14,18-22]
+      --p2 <WINDOWS>
+          Windows of the P2 period [default: 8-10,14-18,22-0]
+      --p3 <WINDOWS>
+          Windows of the P3 period [default: 0-8]
+  -H, --bank-holidays <YYYY-MM-DD,...>
+          Bank holidays, whose hours belong to the P3 period all day long
+      --weekend-period <PERIOD>
+          Period of all the hours of Saturdays and Sundays: \"p1\", \"p2\", \"p3\" or \"windows\"
+          to classify them with the windows of the workdays [default: p3]
+      --day-boundary <POLICY>
+          Type of day applied to the time after midnight of a window that crosses it (e.g.
+          22-2): \"calendar\" uses the type of the day of the reading, \"window-start\" uses the
+          type of the day when the window started [default: calendar]
+      --input-format <FORMAT>
+          Format of the input files, the distributor or platform that exported them:
+          \"edistribucion\", \"datadis\", \"ide\" (i-DE, Iberdrola), \"ufd\" (UFD, Naturgy) or
+          \"eredes\" (E-REDES, formerly Viesgo); \"ndjson\" reads one JSON object per line
+          with the start time (ts) and the kWh (kwh) of each reading, e.g.
+          {\"ts\": \"2023-01-01T00:00\", \"kwh\": 0.25}, and \"homeassistant\" the history of a
+          cumulative kWh sensor exported from Home Assistant; \"auto\" detects it from the
+          header row of each file [default: auto]
+      --csv-map <timestamp=COLUMN,value=COLUMN,format=PATTERN>
+          Read CSV files of any other format from the columns of the date and time when each
+          reading starts, of its kWh and, optionally, of its reading method (method=COLUMN).
+          The pattern of the timestamps is made of %d, %m, %Y, %H, %M and literal characters
+          (e.g. timestamp=FECHA,value=CONSUMO_KWH,format=%d/%m/%Y %H); neither the columns nor
+          the pattern can contain \",\". It overrides --input-format
+      --input-delimiter <CHAR>
+          Field delimiter of the CSV files: \",\", \";\" or \"tab\" [default: detected from the
+          header row]
+      --input-decimal-separator <CHAR>
+          Decimal separator of the consumption values: \".\" or \",\"; the other one is taken as
+          the thousands separator (e.g. 1.234,567 with \",\") [default: detected from the
+          values]
+      --keep-quarter-hours
+          Don't aggregate quarter-hourly readings into hourly ones before classifying them; it's
+          implied when a window limit isn't o'clock, so that the window is honored
+      --include-future
+          Include the readings after the current time, which some exports fill with zeros or
+          estimations; they are excluded and reported by default
+      --exclude-estimated
+          Exclude the readings that the distributor estimated from the consumption
+      --fail-on-estimated
+          Fail if any reading is estimated
+      --select <FIELDS>
+          Print a CSV table with a row per day and the comma separated fields instead of the
+          totals: date, day_type, total_kwh, p1_kwh, p2_kwh, p3_kwh, cumulative_kwh (kWh
+          since the first day), readings (number of readings) and estimated (number of
+          estimated readings)
+      --where <CONDITION>
+          Only print the days of the --select table whose field meets the condition, e.g.
+          \"total_kwh > 15\" or \"day_type == weekend\"; the operators are ==, !=, <, <=, >
+          and >=. It can be repeated to require several conditions
+      --sort <[-]FIELD,...>
+          Sort the days of the --select table by the fields, descending when prefixed with -
+          (e.g. -total_kwh,date) [default: date]
+      --kpis
+          Print the valley (P3), weekend and base load shares of the consumption and an
+          off-peak score, from 0 to 100, that weighs P3 consumption fully and P2 half
+      --print-effective-windows
+          Print the period of each hour of the day for every type of day and exit; CSV_FILEPATH
+          isn't required
+      --fail-on-quality-issues
+          Fail with exit status 3 if the readings have gaps, duplicates or out-of-order rows;
+          they are always reported
+  -h, --help
+          Print help
+
+Exit status:
+  0 on success, 3 on data quality issues with --fail-on-quality-issues, 1 on any other error
+";
+
+/// Exit status when the data quality check fails.
+pub const DATA_QUALITY_EXIT_CODE: u8 = 3;
+
+const DEFAULT_WINDOWS: [&str; 3] = ["10-14,18-22", "8-10,14-18,22-0", "0-8"];
+
+/// Command-line arguments of the application.
+#[derive(Debug)]
+pub struct Cmd {
+    /// Consumption CSV files downloaded from the distributor, `-` is the standard input; only
+    /// empty when `print_effective_windows` is set.
+    pub csv_filepaths: Vec<PathBuf>,
+    /// Meter counters of each period at the beginning of the CSV data, indexed by period.
+    pub base_counters: [Energy; 3],
+    /// Type of each day; bank holidays belong to P3 all day long.
+    pub calendar: Calendar,
+    /// Period of all the hours of the weekends; `None` classifies them with the windows.
+    pub weekend_period: Option<Period>,
+    /// How to read the CSV files.
+    pub reader_options: reader::Options,
+    /// Include the readings after the current time rather than excluding them.
+    pub include_future: bool,
+    /// Exclude the estimated readings from the consumption.
+    pub exclude_estimated: bool,
+    /// Fail if any reading is estimated.
+    pub fail_on_estimated: bool,
+    /// Fail if the readings have data quality issues.
+    pub fail_on_quality_issues: bool,
+    /// How the time after midnight of a window that crosses it gets its day type.
+    pub day_boundary: DayBoundary,
+    /// Fields of the daily report to print instead of the totals.
+    pub select: Option<Vec<Field>>,
+    /// Conditions that the days of the daily report must meet.
+    pub filters: Vec<Filter>,
+    /// Fields to sort the daily report by.
+    pub sort: Vec<SortKey>,
+    /// Print the consumption indicators after the totals.
+    pub print_kpis: bool,
+    /// Print the periods of each hour instead of processing the CSV file.
+    pub print_effective_windows: bool,
+    day_index: DayIndex,
+}
+
+/// Result of aggregating the readings.
+#[derive(Debug)]
+pub struct Summary {
+    /// Energy consumed in each period, indexed by period.
+    pub consumed: [Energy; 3],
+    /// Meter counters of each period after adding the consumption to the base counters; the
+    /// meter displays their whole kWh.
+    pub counters: [Energy; 3],
+    /// Dates of the first and the last readings, if there are readings.
+    pub dates: Option<(Date, Date)>,
+    /// Number of processed readings, including the excluded estimated ones but not the
+    /// duplicates.
+    pub readings: usize,
+    /// Number of readings after the current time, which aren't processed unless they are
+    /// included.
+    pub future: usize,
+    /// Number of processed readings that the distributor estimated, even if they are excluded.
+    pub estimated: usize,
+    /// Data quality issues found in the readings.
+    pub quality: Quality,
+    /// KPIs of the readings counted in the consumption.
+    pub kpis: Kpis,
+    /// Readings grouped by day; only filled when there are fields to select.
+    pub daily: Daily,
+}
+
+/// Policy to decide the day type of the time after midnight of a window that crosses midnight.
+///
+/// For example, with a `22-2` window, is 01:00 of a bank holiday priced as the bank holiday or as
+/// the workday before, when the window started?
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DayBoundary {
+    /// Every reading has the day type of its own date.
+    Calendar,
+    /// The time after midnight has the day type of the date when its window started.
+    WindowStart,
+}
+
+impl DayBoundary {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "calendar" => Some(DayBoundary::Calendar),
+            "window-start" => Some(DayBoundary::WindowStart),
+            _ => None,
+        }
+    }
+}
+
+impl Cmd {
+    /// Parses the arguments, excluding the program name.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+        let mut csv_filepaths = Vec::new();
+        let mut base_counters = [Energy(0); 3];
+        let mut bank_holidays = Vec::new();
+        let mut windows = DEFAULT_WINDOWS.map(String::from);
+        let mut day_boundary = DayBoundary::Calendar;
+        let mut weekend_period = Some(Period::P3);
+        let mut reader_options = reader::Options::default();
+        let mut include_future = false;
+        let mut exclude_estimated = false;
+        let mut fail_on_estimated = false;
+        let mut fail_on_quality_issues = false;
+        let mut select = None;
+        let mut filters = Vec::new();
+        let mut sort = Vec::new();
+        let mut print_kpis = false;
+        let mut print_effective_windows = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| Error::args(format!("{flag} requires a value")))
+            };
+
+            match flag.as_str() {
+                "-c" | "--base-counters" => {
+                    base_counters = utils::parse_meter_counter(&value()?)
+                        .map_err(|e| Error::args(format!("{flag}: {e}")))?
+                }
+                "--p1" | "--p2" | "--p3" => {
+                    let p = Period::parse(&flag[2..]).expect("flag is a period identifier");
+                    windows[p.index()] = value()?;
+                }
+                "-H" | "--bank-holidays" => {
+                    let dates = utils::parse_dates(&value()?)
+                        .map_err(|e| Error::args(format!("{flag}: {e}")))?;
+                    bank_holidays.extend(dates);
+                }
+                "--weekend-period" => {
+                    let v = value()?;
+                    weekend_period = match v.as_str() {
+                        "windows" => None,
+                        _ => Some(Period::parse(&v).ok_or_else(|| {
+                            Error::args(format!("{flag}: '{v}' isn't p1, p2, p3 nor windows"))
+                        })?),
+                    };
+                }
+                "--day-boundary" => {
+                    let v = value()?;
+                    day_boundary = DayBoundary::parse(&v).ok_or_else(|| {
+                        Error::args(format!("{flag}: '{v}' isn't calendar nor window-start"))
+                    })?;
+                }
+                "--input-format" => {
+                    let v = value()?;
+                    reader_options.format = match v.as_str() {
+                        "auto" => None,
+                        _ => Some(reader::Format::parse(&v).ok_or_else(|| {
+                            let names: Vec<&str> =
+                                reader::Format::ALL.iter().map(|f| f.name()).collect();
+                            Error::args(format!(
+                                "{flag}: '{v}' isn't auto nor any of {}",
+                                names.join(", ")
+                            ))
+                        })?),
+                    };
+                }
+                "--csv-map" => {
+                    reader_options.column_map = Some(
+                        utils::parse_csv_map(&value()?)
+                            .map_err(|e| Error::args(format!("{flag}: {e}")))?,
+                    );
+                }
+                "--input-delimiter" => {
+                    let v = value()?;
+                    reader_options.delimiter = Some(match v.as_str() {
+                        "," | ";" => v.chars().next().expect("value isn't empty"),
+                        "tab" | "\t" => '\t',
+                        _ => return Err(Error::args(format!("{flag}: '{v}' isn't , ; nor tab"))),
+                    });
+                }
+                "--input-decimal-separator" => {
+                    let v = value()?;
+                    reader_options.decimal_separator = Some(match v.as_str() {
+                        "." => '.',
+                        "," => ',',
+                        _ => return Err(Error::args(format!("{flag}: '{v}' isn't . nor ,"))),
+                    });
+                }
+                "--keep-quarter-hours" => reader_options.keep_quarter_hours = true,
+                "--include-future" => include_future = true,
+                "--exclude-estimated" => exclude_estimated = true,
+                "--fail-on-estimated" => fail_on_estimated = true,
+                "--fail-on-quality-issues" => fail_on_quality_issues = true,
+                "--select" => {
+                    select = Some(
+                        utils::parse_fields(&value()?)
+                            .map_err(|e| Error::args(format!("{flag}: {e}")))?,
+                    );
+                }
+                "--where" => filters.push(
+                    Filter::parse(&value()?).map_err(|e| Error::args(format!("{flag}: {e}")))?,
+                ),
+                "--sort" => {
+                    sort = value()?
+                        .split(',')
+                        .map(SortKey::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| Error::args(format!("{flag}: {e}")))?;
+                }
+                "--kpis" => print_kpis = true,
+                "--print-effective-windows" => print_effective_windows = true,
+                f if f.starts_with('-') && f.len() > 1 => {
+                    return Err(Error::args(format!("unknown option {f}")))
+                }
+                _ => {
+                    if arg == "-" && csv_filepaths.iter().any(|p| p == Path::new("-")) {
+                        return Err(Error::args("the standard input can only be read once"));
+                    }
+
+                    csv_filepaths.extend(utils::expand_glob(&arg).map_err(Error::args)?);
+                }
+            }
+        }
+
+        let mut parsed = [vec![], vec![], vec![]];
+        for p in Period::ALL {
+            parsed[p.index()] = utils::parse_windows(&windows[p.index()])
+                .map_err(|e| Error::args(format!("--{}: {e}", p.to_string().to_lowercase())))?;
+        }
+        let day_index = DayIndex::new([&parsed[0], &parsed[1], &parsed[2]]).map_err(Error::args)?;
+        // The hourly readings would put the quarters of an hour in the period of the first one.
+        if !day_index.is_hour_aligned() {
+            reader_options.keep_quarter_hours = true;
+        }
+
+        if select.is_none() && !(filters.is_empty() && sort.is_empty()) {
+            return Err(Error::args("--where and --sort require --select"));
+        }
+        if csv_filepaths.is_empty() && !print_effective_windows {
+            return Err(Error::args("CSV_FILEPATH is required"));
+        }
+
+        Ok(Cmd {
+            csv_filepaths,
+            base_counters,
+            calendar: Calendar::new(bank_holidays),
+            weekend_period,
+            reader_options,
+            include_future,
+            exclude_estimated,
+            fail_on_estimated,
+            fail_on_quality_issues,
+            day_boundary,
+            select,
+            filters,
+            sort,
+            print_kpis,
+            print_effective_windows,
+            day_index,
+        })
+    }
+
+    /// Classifies every reading into its period and adds up the consumption of each period; a
+    /// reading of a time already read is a duplicate and it's ignored. `now` is the current local
+    /// date and minute of the day, which tells the future readings.
+    ///
+    /// It fails on the first reading that can't be read.
+    pub fn run(
+        &self,
+        readings: impl IntoIterator<Item = Result<Reading, Error>>,
+        now: (Date, u16),
+    ) -> Result<Summary, Error> {
+        let mut summary = Summary {
+            consumed: [Energy(0); 3],
+            counters: self.base_counters,
+            dates: None,
+            readings: 0,
+            future: 0,
+            estimated: 0,
+            quality: Quality::default(),
+            kpis: Kpis::default(),
+            daily: Daily::default(),
+        };
+
+        for r in readings {
+            let r = r?;
+            if (r.date, r.minute) > now {
+                summary.future += 1;
+                if !self.include_future {
+                    continue;
+                }
+            }
+
+            if !summary.quality.check(&r) {
+                continue;
+            }
+
+            summary.readings += 1;
+            summary.dates = Some(match summary.dates {
+                None => (r.date, r.date),
+                Some((first, last)) => (first.min(r.date), last.max(r.date)),
+            });
+
+            let period = self.period_for(r.date, r.minute);
+            let day_type = self.calendar.day_type(r.date);
+            let counted = !(r.estimated && self.exclude_estimated);
+            if self.select.is_some() {
+                summary.daily.add(&r, period, day_type, counted);
+            }
+            if r.estimated {
+                summary.estimated += 1;
+            }
+            if counted {
+                let consumed = &mut summary.consumed[period.index()];
+                *consumed = consumed
+                    .checked_add(r.energy)
+                    .ok_or(Error::Overflow(period))?;
+                summary.kpis.add(&r, period, day_type);
+            }
+        }
+
+        for p in Period::ALL {
+            let i = p.index();
+            summary.counters[i] = summary.counters[i]
+                .checked_add(summary.consumed[i])
+                .ok_or(Error::Overflow(p))?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Returns the period which a reading starting at `minute` of the day of `date` belongs to,
+    /// applying the day boundary policy.
+    pub fn period_for(&self, date: Date, minute: u16) -> Period {
+        let day = match self.day_boundary {
+            DayBoundary::WindowStart if self.day_index.starts_previous_day(minute) => {
+                date.previous()
+            }
+            _ => date,
+        };
+
+        self.period_of(self.calendar.day_type(day), minute)
+    }
+
+    /// Returns the period which `minute` of any day of `day_type` belongs to.
+    pub fn period_of(&self, day_type: DayType, minute: u16) -> Period {
+        match day_type {
+            DayType::BankHoliday => Period::P3,
+            DayType::Weekend => self
+                .weekend_period
+                .unwrap_or_else(|| self.day_index.get(minute)),
+            DayType::Workday => self.day_index.get(minute),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the arguments with a `22-2` P2 window and 2023-01-06, a Friday, as bank holiday.
+    fn cmd(day_boundary: &str) -> Cmd {
+        let args = [
+            "--p1=8-22",
+            "--p2=22-2",
+            "--p3=2-8",
+            "--bank-holidays=2023-01-06",
+            "--weekend-period=p3",
+            "--day-boundary",
+            day_boundary,
+            "consumption.csv",
+        ];
+        Cmd::from_args(args.map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn windows_not_on_the_hour_keep_quarter_hours() {
+        let args = [
+            "--p1=10:30-14,18-22",
+            "--p2=8-10:30,14-18,22-0",
+            "consumption.csv",
+        ];
+        let cmd = Cmd::from_args(args.map(String::from)).unwrap();
+        assert!(cmd.reader_options.keep_quarter_hours);
+
+        let cmd = Cmd::from_args(["consumption.csv".to_string()]).unwrap();
+        assert!(!cmd.reader_options.keep_quarter_hours);
+    }
+
+    fn date(s: &str) -> Date {
+        Date::parse_iso(s).unwrap()
+    }
+
+    fn reading(date: Date, hour: u16) -> Result<Reading, Error> {
+        Ok(Reading {
+            date,
+            minute: hour * 60,
+            minutes: 60,
+            energy: Energy(250_000),
+            estimated: false,
+            repeated: false,
+        })
+    }
+
+    #[test]
+    fn run_excludes_the_future_readings() {
+        let day = date("2023-01-02");
+        let readings = || (0..24).map(|h| reading(day, h));
+        let now = (day, 12 * 60 + 30);
+
+        let summary = cmd("calendar").run(readings(), now).unwrap();
+        assert_eq!((summary.readings, summary.future), (13, 11));
+        assert_eq!(summary.consumed.iter().map(|e| e.0).sum::<u64>(), 3_250_000);
+
+        let mut cmd = cmd("calendar");
+        cmd.include_future = true;
+        let summary = cmd.run(readings(), now).unwrap();
+        assert_eq!((summary.readings, summary.future), (24, 11));
+        assert_eq!(summary.consumed.iter().map(|e| e.0).sum::<u64>(), 6_000_000);
+    }
+
+    #[test]
+    fn run_adds_up_the_daily_report_and_the_totals_exactly() {
+        let mut cmd = cmd("calendar");
+        cmd.select = Some(vec![Field::TotalKwh]);
+        // 0.1 kWh 10 times isn't 1 kWh with floating point, and each 0.0004 kWh rounded to Wh is 0.
+        let readings = (0..20).map(|h| {
+            let kwh = if h % 2 == 0 { "0.1" } else { "0.0004" };
+            Ok(Reading {
+                energy: Energy::parse_kwh(kwh).unwrap(),
+                ..reading(date("2023-01-02"), h)?
+            })
+        });
+
+        let summary = cmd.run(readings, (date("2023-02-01"), 0)).unwrap();
+        let total = Energy(summary.consumed.iter().map(|e| e.0).sum());
+        assert_eq!(total, Energy(1_004_000));
+        let days = summary.daily.into_days();
+        assert_eq!(days[0].total(), total);
+        assert_eq!(days[0].cumulative, total);
+    }
+
+    #[test]
+    fn run_adds_up_the_readings_by_period() {
+        let mut cmd = cmd("calendar");
+        cmd.base_counters = [
+            Energy(100_000_000),
+            Energy(200_000_000),
+            Energy(300_000_000),
+        ];
+        let day = date("2023-01-02");
+        // The repeated reading of 05:00 isn't counted.
+        let readings = (0..24).chain([5]).map(|h| reading(day, h));
+
+        let summary = cmd.run(readings, (date("2023-02-01"), 0)).unwrap();
+        assert_eq!(
+            summary.consumed,
+            [Energy(3_500_000), Energy(1_000_000), Energy(1_500_000)]
+        );
+        assert_eq!(
+            summary.counters,
+            [
+                Energy(103_500_000),
+                Energy(201_000_000),
+                Energy(301_500_000)
+            ]
+        );
+        assert_eq!(summary.dates, Some((day, day)));
+        assert_eq!((summary.readings, summary.estimated), (24, 0));
+        assert!(summary.quality.has_issues());
+    }
+
+    #[test]
+    fn run_excludes_the_estimated_readings() {
+        let mut cmd = cmd("calendar");
+        cmd.exclude_estimated = true;
+        let day = date("2023-01-02");
+        let readings = (0..24).map(|h| {
+            Ok(Reading {
+                estimated: h < 8,
+                ..reading(day, h)?
+            })
+        });
+
+        let summary = cmd.run(readings, (date("2023-02-01"), 0)).unwrap();
+        assert_eq!(
+            summary.consumed,
+            [Energy(3_500_000), Energy(500_000), Energy(0)]
+        );
+        assert_eq!((summary.readings, summary.estimated), (24, 8));
+    }
+
+    #[test]
+    fn print_effective_windows_without_csv() {
+        let cmd = Cmd::from_args(["--print-effective-windows".to_string()]).unwrap();
+        assert!(cmd.print_effective_windows);
+        assert!(cmd.csv_filepaths.is_empty());
+
+        assert!(Cmd::from_args([]).is_err());
+    }
+
+    #[test]
+    fn several_csv_filepaths() {
+        let args = ["2023.csv", "-", "2022.csv"];
+        let cmd = Cmd::from_args(args.map(String::from)).unwrap();
+        assert_eq!(cmd.csv_filepaths, args.map(PathBuf::from));
+
+        let e = Cmd::from_args(["-", "-"].map(String::from)).err().unwrap();
+        assert!(e
+            .to_string()
+            .ends_with("the standard input can only be read once"));
+    }
+
+    #[test]
+    fn day_types() {
+        let args = ["--bank-holidays=2023-01-06", "consumption.csv"];
+        let cmd = Cmd::from_args(args.map(String::from)).unwrap();
+        let holiday = Date::new(2023, 1, 6).unwrap();
+        let workday = Date::new(2023, 1, 5).unwrap();
+
+        assert_eq!(cmd.calendar.day_type(holiday), DayType::BankHoliday);
+        assert_eq!(cmd.calendar.day_type(workday), DayType::Workday);
+        assert_eq!(cmd.period_for(holiday, 12 * 60), Period::P3);
+        assert_eq!(cmd.period_for(workday, 12 * 60), Period::P1);
+        assert_eq!(cmd.period_of(DayType::Workday, 9 * 60), Period::P2);
+        assert_eq!(cmd.period_of(DayType::Workday, 3 * 60), Period::P3);
+    }
+
+    #[test]
+    fn weekend_period() {
+        let saturday = date("2023-01-07");
+        let cmd = Cmd::from_args(["consumption.csv".to_string()]).unwrap();
+        assert_eq!(cmd.period_for(saturday, 12 * 60), Period::P3);
+
+        let period = |weekend_period: &str| {
+            let args = [weekend_period, "consumption.csv"];
+            let cmd = Cmd::from_args(args.map(String::from)).unwrap();
+            cmd.period_for(saturday, 12 * 60)
+        };
+        assert_eq!(period("--weekend-period=p2"), Period::P2);
+        assert_eq!(period("--weekend-period=windows"), Period::P1);
+
+        let args = ["--weekend-period=p4", "consumption.csv"];
+        let e = Cmd::from_args(args.map(String::from)).err().unwrap();
+        assert!(e.to_string().ends_with("'p4' isn't p1, p2, p3 nor windows"));
+    }
+
+    #[test]
+    fn period_for_calendar_day_boundary() {
+        let cmd = cmd("calendar");
+
+        // Eve of the bank holiday.
+        assert_eq!(cmd.period_for(date("2023-01-05"), 23 * 60), Period::P2);
+        // Bank holiday.
+        assert_eq!(cmd.period_for(date("2023-01-06"), 60), Period::P3);
+        assert_eq!(cmd.period_for(date("2023-01-06"), 23 * 60), Period::P3);
+        assert_eq!(cmd.period_for(date("2023-01-07"), 60), Period::P3);
+        // Monday after the weekend.
+        assert_eq!(cmd.period_for(date("2023-01-08"), 23 * 60), Period::P3);
+        assert_eq!(cmd.period_for(date("2023-01-09"), 60), Period::P2);
+    }
+
+    #[test]
+    fn period_for_window_start_day_boundary() {
+        let cmd = cmd("window-start");
+
+        // Eve of the bank holiday: the window that starts on it is a workday one.
+        assert_eq!(cmd.period_for(date("2023-01-05"), 23 * 60), Period::P2);
+        assert_eq!(cmd.period_for(date("2023-01-06"), 60), Period::P2);
+        // Bank holiday.
+        assert_eq!(cmd.period_for(date("2023-01-06"), 23 * 60), Period::P3);
+        assert_eq!(cmd.period_for(date("2023-01-07"), 60), Period::P3);
+        // Monday after the weekend: the window started on Sunday.
+        assert_eq!(cmd.period_for(date("2023-01-08"), 23 * 60), Period::P3);
+        assert_eq!(cmd.period_for(date("2023-01-09"), 60), Period::P3);
+        // The time out of the window has the day type of its own date.
+        assert_eq!(cmd.period_for(date("2023-01-09"), 2 * 60), Period::P3);
+        assert_eq!(cmd.period_for(date("2023-01-09"), 8 * 60), Period::P1);
+    }
+}
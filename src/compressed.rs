@@ -0,0 +1,554 @@
+//! Decompression of gzip and zip files, detected by their magic bytes.
+
+use std::io::{self, BufRead, Cursor, Read};
+
+use crate::inflate::Inflate;
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Compression formats of the input files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zip,
+}
+
+impl Compression {
+    /// Detects the compression from the first bytes of a file.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if magic.starts_with(ZIP_MAGIC) {
+            Some(Compression::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reader of the decompressed `input`, which is decompressed as it's read.
+    ///
+    /// For zip archives, its content is the one of the only CSV file, which is the only file or
+    /// the only one with `.csv` extension. Their directory is at their end, so the archive is
+    /// read in memory, but not the decompressed file.
+    pub fn decoder(self, input: impl BufRead + 'static) -> io::Result<Box<dyn BufRead>> {
+        match self {
+            Compression::Gzip => Ok(Box::new(Gunzip::new(input).map_err(gzip_error)?)),
+            Compression::Zip => unzip_csv(input),
+        }
+    }
+}
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Describes the errors of the content of a gzip file as so.
+fn gzip_error(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+            invalid_data(format!("invalid gzip file: {e}"))
+        }
+        _ => e,
+    }
+}
+
+/// Decompresses all the members of a gzip file, checking the CRC of each one.
+struct Gunzip<R> {
+    inflate: Inflate<R>,
+    /// CRC of the read bytes of the current member.
+    crc: u32,
+    done: bool,
+}
+
+impl<R: BufRead> Gunzip<R> {
+    fn new(mut input: R) -> io::Result<Self> {
+        if !read_gzip_header(&mut input)? {
+            return Err(invalid_data("not a gzip file"));
+        }
+
+        Ok(Gunzip {
+            inflate: Inflate::new(input),
+            crc: 0,
+            done: false,
+        })
+    }
+
+    /// Checks the trailer of the member that has just been read and starts the next one, if any.
+    fn next_member(&mut self) -> io::Result<()> {
+        let input = self.inflate.get_mut();
+        let mut trailer = [0; 8];
+        input.read_exact(&mut trailer).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid_data("truncated trailer"),
+            _ => e,
+        })?;
+        if u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != self.crc {
+            return Err(invalid_data("CRC mismatch, the file is corrupted"));
+        }
+
+        if read_gzip_header(input)? {
+            self.inflate.restart();
+            self.crc = 0;
+        } else {
+            self.done = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Gunzip<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.fill_buf()?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Gunzip<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while !self.done && self.inflate.fill_buf().map_err(gzip_error)?.is_empty() {
+            self.next_member().map_err(gzip_error)?;
+        }
+
+        Ok(self.inflate.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let data = self.inflate.buffer();
+        self.crc = crc32(self.crc, &data[..amt.min(data.len())]);
+        self.inflate.consume(amt);
+    }
+}
+
+/// Reads the header of a gzip member; returns `false` if the input doesn't start with one, which
+/// is how the data after the last member is ignored.
+fn read_gzip_header(input: &mut impl BufRead) -> io::Result<bool> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    let truncated = |e: io::Error| match e.kind() {
+        io::ErrorKind::UnexpectedEof => invalid_data("truncated header"),
+        _ => e,
+    };
+    let skip = |input: &mut dyn BufRead, n: u64| -> io::Result<()> {
+        if io::copy(&mut input.take(n), &mut io::sink())? < n {
+            return Err(invalid_data("truncated header"));
+        }
+        Ok(())
+    };
+
+    let mut magic = Vec::with_capacity(GZIP_MAGIC.len());
+    input
+        .by_ref()
+        .take(GZIP_MAGIC.len() as u64)
+        .read_to_end(&mut magic)?;
+    if magic != GZIP_MAGIC {
+        return Ok(false);
+    }
+
+    let mut header = [0; 8];
+    input.read_exact(&mut header).map_err(truncated)?;
+    if header[0] != 8 {
+        return Err(invalid_data("unsupported compression method"));
+    }
+
+    let flags = header[1];
+    if flags & FEXTRA != 0 {
+        let mut len = [0; 2];
+        input.read_exact(&mut len).map_err(truncated)?;
+        skip(input, u64::from(u16::from_le_bytes(len)))?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let mut field = Vec::new();
+            input.read_until(0, &mut field)?;
+            if field.last() != Some(&0) {
+                return Err(invalid_data("truncated header"));
+            }
+        }
+    }
+    if flags & FHCRC != 0 {
+        skip(input, 2)?;
+    }
+
+    Ok(true)
+}
+
+/// A file in a zip archive, from its central directory entry.
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    local_header: usize,
+}
+
+fn unzip_csv(mut input: impl Read) -> io::Result<Box<dyn BufRead>> {
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    let entries =
+        zip_entries(&data).map_err(|e| invalid_data(format!("invalid zip archive: {e}")))?;
+    let files: Vec<&ZipEntry> = entries.iter().filter(|e| !e.name.ends_with('/')).collect();
+    let csvs: Vec<&ZipEntry> = files
+        .iter()
+        .copied()
+        .filter(|e| e.name.to_ascii_lowercase().ends_with(".csv"))
+        .collect();
+
+    let entry = match (files.as_slice(), csvs.as_slice()) {
+        ([file], _) | (_, [file]) => file,
+        ([], _) => return Err(invalid_data("the zip archive is empty")),
+        (_, []) => return Err(invalid_data("the zip archive doesn't contain any CSV file")),
+        (_, csvs) => {
+            let names: Vec<&str> = csvs.iter().map(|e| e.name.as_str()).collect();
+            return Err(invalid_data(format!(
+                "the zip archive contains several CSV files: {}",
+                names.join(", ")
+            )));
+        }
+    };
+
+    entry.open(data).map_err(|e| entry.error(e))
+}
+
+fn zip_entries(data: &[u8]) -> Result<Vec<ZipEntry>, String> {
+    const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+    const CENTRAL_SIGNATURE: &[u8] = b"PK\x01\x02";
+
+    let u16_at = |pos: usize| -> Result<usize, String> {
+        let b = data.get(pos..pos + 2).ok_or("truncated archive")?;
+        Ok(u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |pos: usize| -> Result<u32, String> {
+        let b = data.get(pos..pos + 4).ok_or("truncated archive")?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    // The end of central directory record is at the end, only followed by a comment.
+    let eocd = (0..data.len().saturating_sub(21))
+        .rev()
+        .find(|&i| data[i..].starts_with(EOCD_SIGNATURE))
+        .ok_or("end of central directory not found")?;
+    let count = u16_at(eocd + 10)?;
+    let mut pos = u32_at(eocd + 16)? as usize;
+
+    let mut entries = Vec::with_capacity(count);
+    for _ in 0..count {
+        if !data
+            .get(pos..)
+            .is_some_and(|d| d.starts_with(CENTRAL_SIGNATURE))
+        {
+            return Err("invalid central directory entry".into());
+        }
+
+        let name_len = u16_at(pos + 28)?;
+        let name = data
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or("truncated archive")?;
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(pos + 10)? as u16,
+            crc: u32_at(pos + 16)?,
+            compressed_size: u32_at(pos + 20)? as usize,
+            local_header: u32_at(pos + 42)? as usize,
+        });
+        pos += 46 + name_len + u16_at(pos + 30)? + u16_at(pos + 32)?;
+    }
+
+    Ok(entries)
+}
+
+impl ZipEntry {
+    /// Returns a reader of the content of the entry from `archive`, the whole zip file, which
+    /// checks its CRC at the end.
+    fn open(&self, archive: Vec<u8>) -> io::Result<Box<dyn BufRead>> {
+        let header = self.local_header;
+        let len_at = |pos: usize| -> io::Result<usize> {
+            let b = archive
+                .get(pos..pos + 2)
+                .ok_or_else(|| invalid_data("truncated archive"))?;
+            Ok(u16::from_le_bytes([b[0], b[1]]) as usize)
+        };
+
+        let start = header + 30 + len_at(header + 26)? + len_at(header + 28)?;
+        let end = start + self.compressed_size;
+        if end > archive.len() {
+            return Err(invalid_data("truncated archive"));
+        }
+
+        let mut compressed = Cursor::new(archive);
+        compressed.set_position(start as u64);
+        let compressed = compressed.take(self.compressed_size as u64);
+        let content: Box<dyn BufRead> = match self.method {
+            0 => Box::new(compressed),
+            8 => Box::new(Inflate::new(compressed)),
+            m => return Err(invalid_data(format!("unsupported compression method {m}"))),
+        };
+
+        Ok(Box::new(ZipFile {
+            content,
+            name: self.name.clone(),
+            crc: 0,
+            expected_crc: self.crc,
+        }))
+    }
+
+    fn error(&self, e: io::Error) -> io::Error {
+        zip_file_error(&self.name, e)
+    }
+}
+
+fn zip_file_error(name: &str, e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+            invalid_data(format!("cannot extract {name} from the zip archive: {e}"))
+        }
+        _ => e,
+    }
+}
+
+/// Content of a file of a zip archive, which checks its CRC once it's read up to its end.
+struct ZipFile {
+    content: Box<dyn BufRead>,
+    name: String,
+    /// CRC of the read bytes.
+    crc: u32,
+    expected_crc: u32,
+}
+
+impl Read for ZipFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.fill_buf()?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for ZipFile {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let name = &self.name;
+        let data = self
+            .content
+            .fill_buf()
+            .map_err(|e| zip_file_error(name, e))?;
+        if data.is_empty() && self.crc != self.expected_crc {
+            return Err(zip_file_error(
+                name,
+                invalid_data("CRC mismatch, the file is corrupted"),
+            ));
+        }
+
+        Ok(data)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // The content is in memory, so the bytes to consume are buffered and this doesn't read.
+        if let Ok(data) = self.content.fill_buf() {
+            self.crc = crc32(self.crc, &data[..amt.min(data.len())]);
+        }
+        self.content.consume(amt);
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+
+    table
+}
+
+/// Updates the CRC-32 `crc` of the previous bytes with the next `data`; the CRC of no bytes is 0.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &b| {
+        CRC32_TABLE[((crc ^ u32::from(b)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &[u8] = b"CUPS,Fecha,Hora,Consumo_kWh\nES0031,01/01/2023,1,0.110\n";
+
+    /// Compresses `content` as a raw DEFLATE stream of a single stored block.
+    fn decompress(compression: Compression, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut out = Vec::new();
+        compression
+            .decoder(Cursor::new(data.to_vec()))
+            .and_then(|mut content| content.read_to_end(&mut out))
+            .map_err(|e| e.to_string())?;
+        Ok(out)
+    }
+
+    fn deflate(content: &[u8]) -> Vec<u8> {
+        let len = content.len() as u16;
+        let mut out = vec![0x01];
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(content);
+        out
+    }
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        out.extend(deflate(content));
+        out.extend(crc32(0, content).to_le_bytes());
+        out.extend((content.len() as u32).to_le_bytes());
+        out
+    }
+
+    /// Builds a zip archive of the `(name, content)` files, deflated.
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut central = Vec::new();
+        for (name, content) in files {
+            let compressed = deflate(content);
+            let mut fields = Vec::new();
+            fields.extend([20, 0, 0, 0, 8, 0, 0, 0, 0, 0]);
+            fields.extend(crc32(0, content).to_le_bytes());
+            fields.extend((compressed.len() as u32).to_le_bytes());
+            fields.extend((content.len() as u32).to_le_bytes());
+            fields.extend((name.len() as u16).to_le_bytes());
+            fields.extend([0, 0]);
+
+            central.extend(b"PK\x01\x02\x14\x00");
+            central.extend(&fields);
+            central.extend([0; 10]);
+            central.extend((out.len() as u32).to_le_bytes());
+            central.extend(name.as_bytes());
+
+            out.extend(ZIP_MAGIC);
+            out.extend(&fields);
+            out.extend(name.as_bytes());
+            out.extend(compressed);
+        }
+
+        let offset = out.len() as u32;
+        let count = (files.len() as u16).to_le_bytes();
+        out.extend(&central);
+        out.extend(b"PK\x05\x06\x00\x00\x00\x00");
+        out.extend(count);
+        out.extend(count);
+        out.extend((central.len() as u32).to_le_bytes());
+        out.extend(offset.to_le_bytes());
+        out.extend([0, 0]);
+        out
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(Compression::detect(&gzip(CSV)), Some(Compression::Gzip));
+        assert_eq!(
+            Compression::detect(&zip(&[("a.csv", CSV)])),
+            Some(Compression::Zip)
+        );
+        assert_eq!(Compression::detect(CSV), None);
+    }
+
+    #[test]
+    fn gzip_members() {
+        let mut data = gzip(b"first,");
+        data.extend(gzip(b"second"));
+
+        assert_eq!(
+            decompress(Compression::Gzip, &data),
+            Ok(b"first,second".to_vec())
+        );
+    }
+
+    #[test]
+    fn gzip_crc_mismatch() {
+        let mut data = gzip(CSV);
+        let crc = data.len() - 8;
+        data[crc] ^= 0xff;
+
+        assert_eq!(
+            decompress(Compression::Gzip, &data),
+            Err("invalid gzip file: CRC mismatch, the file is corrupted".into())
+        );
+    }
+
+    #[test]
+    fn zip_csv() {
+        let data = zip(&[("readme.txt", b"readme"), ("consumption.CSV", CSV)]);
+
+        assert_eq!(decompress(Compression::Zip, &data), Ok(CSV.to_vec()));
+    }
+
+    #[test]
+    fn zip_crc_mismatch() {
+        let mut data = zip(&[("a.csv", CSV)]);
+        // CRC of the central directory entry, which is the one checked.
+        let crc = data.len() - 22 - (46 + "a.csv".len()) + 16;
+        data[crc] ^= 0xff;
+
+        assert_eq!(
+            decompress(Compression::Zip, &data),
+            Err(
+                "cannot extract a.csv from the zip archive: CRC mismatch, the file is corrupted"
+                    .into()
+            )
+        );
+    }
+
+    #[test]
+    fn zip_several_csvs() {
+        let data = zip(&[("a.csv", CSV), ("b.csv", CSV)]);
+
+        assert_eq!(
+            decompress(Compression::Zip, &data),
+            Err("the zip archive contains several CSV files: a.csv, b.csv".into())
+        );
+    }
+
+    #[test]
+    fn zip_without_csv() {
+        let data = zip(&[("a.txt", b"a"), ("b.txt", b"b")]);
+
+        assert_eq!(
+            decompress(Compression::Zip, &data),
+            Err("the zip archive doesn't contain any CSV file".into())
+        );
+    }
+
+    #[test]
+    fn truncated_files() {
+        let gzip = gzip(CSV);
+        for len in GZIP_MAGIC.len()..gzip.len() {
+            assert!(
+                decompress(Compression::Gzip, &gzip[..len]).is_err(),
+                "{len} bytes"
+            );
+        }
+
+        let zip = zip(&[("a.csv", CSV)]);
+        for len in ZIP_MAGIC.len()..zip.len() {
+            assert!(
+                decompress(Compression::Zip, &zip[..len]).is_err(),
+                "{len} bytes"
+            );
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt;
+
+/// A calendar date of the proleptic Gregorian calendar.
+///
+/// Dates are ordered chronologically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    /// Returns `None` when the date doesn't exist (e.g. 2023-02-29).
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+
+        Some(Date { year, month, day })
+    }
+
+    pub fn year(self) -> i32 {
+        self.year
+    }
+
+    /// Returns the ISO weekday number, from 1 (Monday) to 7 (Sunday).
+    pub fn weekday(self) -> u8 {
+        // Days since 1970-01-01, which was a Thursday.
+        let days = self.days_since_epoch();
+        ((days + 3).rem_euclid(7) + 1) as u8
+    }
+
+    /// Returns the number of days from 1970-01-01 to this date, negative for earlier dates.
+    pub fn days_since_epoch(self) -> i64 {
+        // Algorithm from Howard Hinnant's "days_from_civil".
+        let y = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = i64::from(self.month);
+        let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    /// Returns the date which is `days` days after 1970-01-01 (before it, when negative).
+    pub fn from_days_since_epoch(days: i64) -> Self {
+        // Algorithm from Howard Hinnant's "civil_from_days".
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+
+        Date { year, month, day }
+    }
+
+    /// Returns the day before.
+    pub fn previous(self) -> Self {
+        match (self.month, self.day) {
+            (1, 1) => Date {
+                year: self.year - 1,
+                month: 12,
+                day: 31,
+            },
+            (m, 1) => Date {
+                year: self.year,
+                month: m - 1,
+                day: days_in_month(self.year, m - 1),
+            },
+            (m, d) => Date {
+                year: self.year,
+                month: m,
+                day: d - 1,
+            },
+        }
+    }
+
+    /// Parses a date in `YYYY-MM-DD` format.
+    pub fn parse_iso(s: &str) -> Option<Self> {
+        let mut parts = s.split('-');
+        let (y, m, d) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || y.len() != 4 || m.len() != 2 || d.len() != 2 {
+            return None;
+        }
+
+        Date::new(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?)
+    }
+
+    /// Parses a date in `DD/MM/YYYY` format, the one used by e-distribución exports.
+    pub fn parse_dmy(s: &str) -> Option<Self> {
+        let mut parts = s.split('/');
+        let (d, m, y) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || y.len() != 4 || m.is_empty() || d.is_empty() {
+            return None;
+        }
+
+        Date::new(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?)
+    }
+
+    /// Parses a date in `YYYY/MM/DD` format, the one used by Datadis exports.
+    pub fn parse_ymd(s: &str) -> Option<Self> {
+        let mut parts = s.split('/');
+        let (y, m, d) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() || y.len() != 4 || m.is_empty() || d.is_empty() {
+            return None;
+        }
+
+        Date::new(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let date = Date::new(2024, 2, 29).unwrap();
+        assert_eq!(Date::parse_iso("2024-02-29"), Some(date));
+        assert_eq!(Date::parse_dmy("29/02/2024"), Some(date));
+        assert_eq!(Date::parse_dmy("1/2/2024"), Date::new(2024, 2, 1));
+        assert_eq!(date.to_string(), "2024-02-29");
+
+        for s in [
+            "2023-02-29",
+            "2023-2-01",
+            "2023-13-01",
+            "2023-01-01-01",
+            "01/01/2023",
+        ] {
+            assert_eq!(Date::parse_iso(s), None, "{s}");
+        }
+        for s in ["29/02/2023", "01/01/23", "/01/2023", "2023-01-01"] {
+            assert_eq!(Date::parse_dmy(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn order() {
+        assert!(Date::new(2023, 12, 31) < Date::new(2024, 1, 1));
+        assert!(Date::new(2024, 1, 31) < Date::new(2024, 2, 1));
+    }
+
+    #[test]
+    fn weekdays() {
+        let date = |y, m, d| Date::new(y, m, d).unwrap();
+
+        assert_eq!(date(1970, 1, 1).days_since_epoch(), 0);
+        assert_eq!(date(1969, 12, 31).days_since_epoch(), -1);
+        assert_eq!(date(2000, 3, 1).days_since_epoch(), 11_017);
+        assert_eq!(date(1970, 1, 1).weekday(), 4);
+        assert_eq!(date(2023, 1, 2).weekday(), 1);
+        assert_eq!(date(2024, 2, 29).weekday(), 4);
+        assert_eq!(date(2023, 1, 8).weekday(), 7);
+        assert_eq!(date(1969, 12, 28).weekday(), 7);
+    }
+}
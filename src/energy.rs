@@ -0,0 +1,128 @@
+//! Energy amounts counted in whole mWh, so that adding up the readings is exact.
+
+use std::fmt;
+
+/// An amount of energy in mWh; it's displayed in kWh with 3 decimals.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Energy(pub u64);
+
+impl Energy {
+    /// mWh of a kWh.
+    pub const KWH: u64 = 1_000_000;
+    /// mWh of a Wh.
+    pub const WH: u64 = 1_000;
+
+    /// Converts `kwh`, rounding it to the closest mWh; `None` if it's negative, not finite or too
+    /// large.
+    pub fn from_kwh(kwh: f64) -> Option<Self> {
+        let mwh = (kwh * Self::KWH as f64).round();
+        // u64::MAX converted to f64 is 2^64, which doesn't fit.
+        (0.0..u64::MAX as f64)
+            .contains(&mwh)
+            .then_some(Energy(mwh as u64))
+    }
+
+    /// Parses a kWh amount separated by `.`, e.g. `1234` or `1234.567`, rounding it to the
+    /// closest mWh.
+    pub fn parse_kwh(s: &str) -> Option<Self> {
+        Self::parse(s, Self::KWH)
+    }
+
+    /// Parses an amount of units of `mwh_per_unit` mWh, a power of 10, whose decimals are
+    /// separated by `.`, e.g. `1234.567` or `.5`; the decimals below the mWh are rounded.
+    pub fn parse(s: &str, mwh_per_unit: u64) -> Option<Self> {
+        let (integer, fraction) = s.split_once('.').unwrap_or((s, ""));
+        let digits = integer.bytes().chain(fraction.bytes());
+        if integer.len() + fraction.len() == 0 || !digits.clone().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut mwh = match integer {
+            "" => 0,
+            _ => integer.parse::<u64>().ok()?.checked_mul(mwh_per_unit)?,
+        };
+        let mut scale = mwh_per_unit;
+        for d in fraction.bytes().map(|b| u64::from(b - b'0')) {
+            if scale == 1 {
+                // The first decimal below the mWh rounds it.
+                mwh = mwh.checked_add(u64::from(d >= 5))?;
+                break;
+            }
+            scale /= 10;
+            mwh = mwh.checked_add(d * scale)?;
+        }
+
+        Some(Energy(mwh))
+    }
+
+    /// Returns `None` if the sum overflows.
+    pub fn checked_add(self, other: Energy) -> Option<Energy> {
+        self.0.checked_add(other.0).map(Energy)
+    }
+
+    /// Returns `u64::MAX` mWh if the sum overflows.
+    pub fn saturating_add(self, other: Energy) -> Energy {
+        Energy(self.0.saturating_add(other.0))
+    }
+
+    /// Returns the amount in kWh, which can't be exact.
+    pub fn kwh(self) -> f64 {
+        self.0 as f64 / Self::KWH as f64
+    }
+}
+
+impl fmt::Display for Energy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Rounded to the closest Wh, without overflowing.
+        let wh = self.0 / Self::WH + u64::from(self.0 % Self::WH >= Self::WH / 2);
+        write!(f, "{}.{:03}", wh / 1000, wh % 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Energy::parse_kwh("1234"), Some(Energy(1_234_000_000)));
+        assert_eq!(Energy::parse_kwh("1234.567"), Some(Energy(1_234_567_000)));
+        assert_eq!(Energy::parse_kwh("0.1234564"), Some(Energy(123_456)));
+        assert_eq!(Energy::parse_kwh("0.1234565"), Some(Energy(123_457)));
+        assert_eq!(Energy::parse_kwh(".5"), Some(Energy(500_000)));
+        assert_eq!(Energy::parse_kwh("1."), Some(Energy(1_000_000)));
+        assert_eq!(Energy::parse("500", Energy::WH), Some(Energy(500_000)));
+        assert_eq!(Energy::parse("0.5", Energy::WH), Some(Energy(500)));
+
+        for s in [
+            "",
+            ".",
+            "-1",
+            "1e3",
+            "1,5",
+            "1.2.3",
+            " 1",
+            "18446744073709.551616",
+        ] {
+            assert_eq!(Energy::parse_kwh(s), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn kwh() {
+        assert_eq!(Energy::from_kwh(0.1 + 0.2), Some(Energy(300_000)));
+        assert_eq!(Energy::from_kwh(-0.1), None);
+        assert_eq!(Energy::from_kwh(f64::NAN), None);
+        assert_eq!(Energy::from_kwh(f64::INFINITY), None);
+        assert_eq!(Energy(1_500_000).kwh(), 1.5);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Energy(0).to_string(), "0.000");
+        assert_eq!(Energy(1_234_567_499).to_string(), "1234.567");
+        assert_eq!(Energy(1_234_567_500).to_string(), "1234.568");
+        assert_eq!(Energy(999_500).to_string(), "1.000");
+        assert_eq!(Energy(u64::MAX).to_string(), "18446744073709.552");
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::period::Period;
+
+/// Errors that the application can report to the user.
+#[derive(Debug)]
+pub enum Error {
+    /// Invalid command-line arguments.
+    Args(String),
+    /// The CSV file couldn't be read; `path` is `-` for the standard input.
+    Io { path: PathBuf, source: io::Error },
+    /// The content of the input file is invalid; `line` is 1-based.
+    Csv {
+        file: Option<PathBuf>,
+        line: usize,
+        msg: String,
+    },
+    /// The output couldn't be written.
+    Output(io::Error),
+    /// Some readings are estimated and the user asked to fail on them.
+    EstimatedReadings(usize),
+    /// The series has gaps, duplicates or out-of-order readings and the user asked to fail on
+    /// them.
+    DataQuality,
+    /// The consumption of the period, or its final counter, is too large to count it in mWh.
+    Overflow(Period),
+}
+
+impl Error {
+    pub fn args(msg: impl Into<String>) -> Self {
+        Error::Args(msg.into())
+    }
+
+    pub fn csv(line: usize, msg: impl Into<String>) -> Self {
+        Error::Csv {
+            file: None,
+            line,
+            msg: msg.into(),
+        }
+    }
+
+    /// Sets the file where the content error happened.
+    pub fn in_file(self, path: &Path) -> Self {
+        match self {
+            Error::Csv { line, msg, .. } => Error::Csv {
+                file: Some(path.to_path_buf()),
+                line,
+                msg,
+            },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Args(msg) => write!(f, "invalid arguments: {msg}"),
+            Error::Io { path, source } if path.as_os_str() == "-" => {
+                write!(f, "cannot read the standard input: {source}")
+            }
+            Error::Io { path, source } => write!(f, "cannot read {}: {source}", path.display()),
+            Error::Csv {
+                file: Some(file),
+                line,
+                msg,
+            } if file.as_os_str() != "-" => {
+                write!(f, "invalid data at {}:{line}: {msg}", file.display())
+            }
+            Error::Csv { line, msg, .. } => write!(f, "invalid data at line {line}: {msg}"),
+            Error::Output(source) => write!(f, "cannot write the output: {source}"),
+            Error::DataQuality => write!(
+                f,
+                "the readings have data quality issues and --fail-on-quality-issues is set"
+            ),
+            Error::EstimatedReadings(n) => write!(
+                f,
+                "{n} readings are estimated and --fail-on-estimated is set"
+            ),
+            Error::Overflow(period) => write!(f, "the {period} consumption is too large to add up"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Output(source) => Some(source),
+            _ => None,
+        }
+    }
+}
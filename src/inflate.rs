@@ -0,0 +1,523 @@
+//! Decoder of DEFLATE compressed data (RFC 1951), the compression used by gzip and zip files.
+//!
+//! It follows the approach of zlib's `puff.c`: canonical Huffman codes are decoded bit by bit,
+//! which is simple and fast enough for the size of consumption exports. The data is decompressed
+//! as it's read, keeping only the last 32 KiB of output that back-references can copy.
+
+use std::io::{self, BufRead, Read};
+
+/// Maximum number of bits of a Huffman code.
+const MAX_BITS: usize = 15;
+/// Maximum distance of the back-references.
+const WINDOW_SIZE: usize = 32 * 1024;
+/// Number of bytes decompressed at once when more output is needed.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which the code lengths of the code lengths alphabet are stored.
+const CODE_LENGTHS_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses the DEFLATE stream that `input` starts with as it's read.
+///
+/// The stream ends with its last block; the input is left right after it, e.g. at the gzip
+/// trailer, and [`Inflate::get_mut`] gives access to it.
+pub struct Inflate<R> {
+    bits: Bits<R>,
+    /// Last decompressed bytes, followed by the ones not read yet from `pos`.
+    window: Vec<u8>,
+    pos: usize,
+    block: Block,
+    /// Whether the current block is the last one of the stream.
+    last: bool,
+}
+
+/// Decompression state of the current block.
+enum Block {
+    /// At the header of the next block.
+    Header,
+    /// In a stored block, with the number of bytes left.
+    Stored(usize),
+    /// In a block compressed with these literal/length and distance codes.
+    Codes(Box<(Huffman, Huffman)>),
+    /// After the last block.
+    End,
+}
+
+impl<R: BufRead> Inflate<R> {
+    pub fn new(input: R) -> Self {
+        Inflate {
+            bits: Bits::new(input),
+            window: Vec::new(),
+            pos: 0,
+            block: Block::Header,
+            last: false,
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.bits.input
+    }
+
+    /// Starts decompressing another stream from the current position of the input.
+    pub fn restart(&mut self) {
+        self.bits.align();
+        self.window.clear();
+        self.pos = 0;
+        self.block = Block::Header;
+        self.last = false;
+    }
+
+    /// Decompressed bytes not read yet, without decompressing more.
+    pub fn buffer(&self) -> &[u8] {
+        &self.window[self.pos..]
+    }
+
+    /// Decompresses up to [`CHUNK_SIZE`] more bytes, or up to the end of the stream, once all
+    /// the previous ones are read.
+    fn decompress(&mut self) -> io::Result<()> {
+        if self.window.len() >= 2 * WINDOW_SIZE {
+            self.window.drain(..self.window.len() - WINDOW_SIZE);
+            self.pos = self.window.len();
+        }
+
+        let limit = self.pos + CHUNK_SIZE;
+        while self.window.len() < limit {
+            match &mut self.block {
+                Block::Header if self.last => self.block = Block::End,
+                Block::Header => {
+                    self.last = self.bits.get(1)? == 1;
+                    self.block = match self.bits.get(2)? {
+                        0 => Block::Stored(stored_len(&mut self.bits)?),
+                        1 => Block::Codes(Box::new(fixed_codes())),
+                        2 => Block::Codes(Box::new(dynamic_codes(&mut self.bits)?)),
+                        _ => return Err(invalid_data("invalid block type")),
+                    };
+                }
+                Block::Stored(0) => self.block = Block::Header,
+                Block::Stored(left) => {
+                    let data = self.bits.input.fill_buf()?;
+                    if data.is_empty() {
+                        return Err(unexpected_end());
+                    }
+
+                    let n = data.len().min(*left).min(limit - self.window.len());
+                    self.window.extend_from_slice(&data[..n]);
+                    self.bits.input.consume(n);
+                    *left -= n;
+                }
+                Block::Codes(codes) => {
+                    let (lit, dist) = &**codes;
+                    if codes_until(&mut self.bits, &mut self.window, lit, dist, limit)? {
+                        self.block = Block::Header;
+                    }
+                }
+                Block::End => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Inflate<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.fill_buf()?;
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Inflate<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.window.len() {
+            self.decompress()?;
+        }
+
+        Ok(self.buffer())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.window.len());
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn unexpected_end() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "unexpected end of data")
+}
+
+/// Reads the bits of the input bytes, least significant bit first, without reading the bytes
+/// that the requested bits don't need.
+struct Bits<R> {
+    input: R,
+    buf: u32,
+    len: u32,
+}
+
+impl<R: BufRead> Bits<R> {
+    fn new(input: R) -> Self {
+        Bits {
+            input,
+            buf: 0,
+            len: 0,
+        }
+    }
+
+    fn get(&mut self, n: u32) -> io::Result<u32> {
+        while self.len < n {
+            let byte = *self.input.fill_buf()?.first().ok_or_else(unexpected_end)?;
+            self.input.consume(1);
+            self.buf |= u32::from(byte) << self.len;
+            self.len += 8;
+        }
+
+        let v = self.buf & ((1 << n) - 1);
+        self.buf >>= n;
+        self.len -= n;
+        Ok(v)
+    }
+
+    /// Discards the remaining bits of the current byte.
+    fn align(&mut self) {
+        self.buf = 0;
+        self.len = 0;
+    }
+}
+
+/// A canonical Huffman code: number of codes of each length and the symbols ordered by code.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+
+        // Reject over-subscribed codes; incomplete ones are allowed, as zlib does.
+        let mut left = 1i32;
+        for &c in &counts[1..] {
+            left = (left << 1) - i32::from(c);
+            if left < 0 {
+                return Err(invalid_data("invalid Huffman code lengths"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for l in 1..=MAX_BITS {
+            offsets[l + 1] = offsets[l] + counts[l];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits<impl BufRead>) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= bits.get(1)? as i32;
+            let count = i32::from(count);
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(invalid_data("invalid Huffman code"))
+    }
+}
+
+/// Reads the length of a stored block, which starts at the next byte.
+fn stored_len(bits: &mut Bits<impl BufRead>) -> io::Result<usize> {
+    bits.align();
+    let len = bits.get(16)? as u16;
+    let nlen = bits.get(16)? as u16;
+    if len != !nlen {
+        return Err(invalid_data("invalid stored block length"));
+    }
+
+    Ok(usize::from(len))
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    (
+        Huffman::new(&lengths).expect("fixed literal/length code is valid"),
+        Huffman::new(&[5; 30]).expect("fixed distance code is valid"),
+    )
+}
+
+fn dynamic_codes(bits: &mut Bits<impl BufRead>) -> io::Result<(Huffman, Huffman)> {
+    let nlen = bits.get(5)? as usize + 257;
+    let ndist = bits.get(5)? as usize + 1;
+    let ncode = bits.get(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(invalid_data("invalid dynamic block header"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTHS_ORDER[..ncode] {
+        code_lengths[i] = bits.get(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_lengths.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let prev = *lengths[..i]
+                    .last()
+                    .ok_or_else(|| invalid_data("repeated code length without a previous one"))?;
+                (prev, 3 + bits.get(2)? as usize)
+            }
+            17 => (0, 3 + bits.get(3)? as usize),
+            _ => (0, 11 + bits.get(7)? as usize),
+        };
+
+        let end = i + repeat;
+        if end > lengths.len() {
+            return Err(invalid_data("too many code lengths"));
+        }
+
+        lengths[i..end].fill(value);
+        i = end;
+    }
+
+    if lengths[256] == 0 {
+        return Err(invalid_data("missing end of block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+/// Decodes the symbols of a compressed block into `out` until it has `limit` bytes, or a few more
+/// if a back-reference crosses the limit; returns whether the end of the block was reached.
+fn codes_until(
+    bits: &mut Bits<impl BufRead>,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> io::Result<bool> {
+    while out.len() < limit {
+        let symbol = lit.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(true),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(invalid_data("invalid length code"));
+                }
+                let len = LENGTH_BASE[i] as usize + bits.get(u32::from(LENGTH_EXTRA[i]))? as usize;
+
+                let i = dist.decode(bits)? as usize;
+                if i >= DIST_BASE.len() {
+                    return Err(invalid_data("invalid distance code"));
+                }
+                let d = DIST_BASE[i] as usize + bits.get(u32::from(DIST_EXTRA[i]))? as usize;
+                if d > out.len() {
+                    return Err(invalid_data("distance too far back"));
+                }
+
+                let start = out.len() - d;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decompresses the stream at the beginning of `data`, returning the decompressed bytes and
+    /// the number of bytes of `data` that the stream takes.
+    fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+        let mut input = data;
+        let mut out = Vec::new();
+        Inflate::new(&mut input)
+            .read_to_end(&mut out)
+            .map_err(|e| e.to_string())?;
+        Ok((out, data.len() - input.len()))
+    }
+
+    /// Writes bits least significant bit first, as DEFLATE streams store them.
+    #[derive(Default)]
+    struct BitWriter {
+        out: Vec<u8>,
+        len: u32,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u32, n: u32) {
+            for i in 0..n {
+                if self.len.is_multiple_of(8) {
+                    self.out.push(0);
+                }
+                *self.out.last_mut().unwrap() |= (((value >> i) & 1) as u8) << (self.len % 8);
+                self.len += 1;
+            }
+        }
+
+        /// Writes a Huffman code, which is stored most significant bit first.
+        fn code(&mut self, code: u32, n: u32) {
+            for i in (0..n).rev() {
+                self.bits(code >> i, 1);
+            }
+        }
+    }
+
+    /// Raw DEFLATE stream of "P1,P2,P3,P1,P2,P3" with a fixed Huffman codes block.
+    const FIXED: [u8; 13] = [
+        0x0b, 0x30, 0xd4, 0x09, 0x30, 0xd2, 0x09, 0x30, 0xd6, 0x09, 0x80, 0x32, 0x00,
+    ];
+
+    /// Raw DEFLATE stream of [`dynamic_content`] with a dynamic Huffman codes block.
+    const DYNAMIC: [u8; 47] = [
+        0x5d, 0xc8, 0xbb, 0x09, 0x00, 0x20, 0x10, 0x44, 0xc1, 0xdc, 0x5a, 0x44, 0x77, 0xcf, 0x7f,
+        0xff, 0x8d, 0x09, 0x26, 0xc2, 0x9b, 0x70, 0xe4, 0x2a, 0xd7, 0x50, 0xb4, 0xec, 0xac, 0xe2,
+        0xa4, 0x1f, 0xc1, 0x68, 0x8c, 0xce, 0x18, 0x8c, 0xc9, 0x58, 0x8c, 0xcd, 0x38, 0x0c, 0xeb,
+        0xcd, 0x05,
+    ];
+
+    fn dynamic_content() -> String {
+        (1..=10).map(|h| format!("01/01/2023,{h},0.1\n")).collect()
+    }
+
+    #[test]
+    fn stored_blocks() {
+        // A non-final block followed by a final one, and the trailing data that isn't part of
+        // the stream.
+        let data = [
+            0x00, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c', 0x01, 0x02, 0x00, 0xfd, 0xff, b'd',
+            b'e', 0xff,
+        ];
+
+        assert_eq!(inflate(&data), Ok((b"abcde".to_vec(), 15)));
+    }
+
+    #[test]
+    fn fixed_block() {
+        assert_eq!(
+            inflate(&FIXED),
+            Ok((b"P1,P2,P3,P1,P2,P3".to_vec(), FIXED.len()))
+        );
+    }
+
+    #[test]
+    fn dynamic_block() {
+        assert_eq!(
+            inflate(&DYNAMIC),
+            Ok((dynamic_content().into_bytes(), DYNAMIC.len()))
+        );
+    }
+
+    #[test]
+    fn far_back_references_with_constant_memory() {
+        // Stored blocks of 100 KiB, followed by a fixed codes block that copies the 258 bytes at
+        // the maximum distance, 32 KiB, 40000 times.
+        let content: Vec<u8> = (0..100 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut data = Vec::new();
+        for block in content.chunks(u16::MAX as usize) {
+            let len = block.len() as u16;
+            data.push(0x00);
+            data.extend(len.to_le_bytes());
+            data.extend((!len).to_le_bytes());
+            data.extend(block);
+        }
+        let mut w = BitWriter::default();
+        w.bits(1, 1);
+        w.bits(1, 2);
+        for _ in 0..40_000 {
+            // Length 258 is the symbol 285, the distance 32768 the symbol 29 plus 8191.
+            w.code(0xC0 + 285 - 280, 8);
+            w.code(29, 5);
+            w.bits(8191, 13);
+        }
+        w.code(0, 7);
+        data.extend(w.out);
+
+        let mut expected = content.clone();
+        for _ in 0..40_000 * 258 {
+            expected.push(expected[expected.len() - WINDOW_SIZE]);
+        }
+
+        let mut inflate = Inflate::new(data.as_slice());
+        let mut out = Vec::new();
+        inflate.read_to_end(&mut out).unwrap();
+        assert!(out == expected);
+        // The output is above 10 MB, but only the window and a chunk are kept.
+        assert!(inflate.window.capacity() <= 4 * WINDOW_SIZE + CHUNK_SIZE);
+    }
+
+    #[test]
+    fn invalid_stored_block_length() {
+        let data = [0x01, 0x03, 0x00, 0x00, 0x00, b'a', b'b', b'c'];
+
+        assert_eq!(inflate(&data), Err("invalid stored block length".into()));
+    }
+
+    #[test]
+    fn invalid_block_type() {
+        assert_eq!(inflate(&[0x07]), Err("invalid block type".into()));
+    }
+
+    #[test]
+    fn truncated_streams() {
+        for stream in [&FIXED[..], &DYNAMIC[..]] {
+            for len in 0..stream.len() {
+                assert!(inflate(&stream[..len]).is_err(), "{len} bytes");
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Key indicators of the consumption profile.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::calendar::DayType;
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::period::Period;
+use crate::reader::Reading;
+
+/// Accumulates the consumption indicators of the readings, in any order.
+#[derive(Debug, Default)]
+pub struct Kpis {
+    total: Energy,
+    periods: [Energy; 3],
+    weekend: Energy,
+    /// Energy and minutes of the reading with the least energy per minute, and minutes of
+    /// readings, of each day.
+    days: HashMap<Date, (Energy, u16, u32)>,
+}
+
+impl Kpis {
+    /// Adds a reading of `period` of a day of `day_type`.
+    pub fn add(&mut self, r: &Reading, period: Period, day_type: DayType) {
+        self.total = self.total.saturating_add(r.energy);
+        let i = period.index();
+        self.periods[i] = self.periods[i].saturating_add(r.energy);
+        if day_type == DayType::Weekend {
+            self.weekend = self.weekend.saturating_add(r.energy);
+        }
+
+        let (min, min_minutes, minutes) =
+            self.days.entry(r.date).or_insert((r.energy, r.minutes, 0));
+        // The energies per minute are compared without dividing them.
+        if u128::from(r.energy.0) * u128::from(*min_minutes)
+            < u128::from(min.0) * u128::from(r.minutes)
+        {
+            (*min, *min_minutes) = (r.energy, r.minutes);
+        }
+        *minutes += u32::from(r.minutes);
+    }
+
+    /// The base load is, every day, the consumption that the lowest reading of the day would
+    /// have had all the day long, in kWh.
+    fn base_load(&self) -> f64 {
+        self.days
+            .values()
+            .map(|&(min, min_minutes, minutes)| {
+                min.kwh() * f64::from(minutes) / f64::from(min_minutes)
+            })
+            .sum()
+    }
+
+    /// Score from 0 to 100 of how much of the consumption is off-peak: the share of the
+    /// consumption in P3 counts fully, the one in P2 counts half and the one in P1 doesn't count.
+    /// It's a fixed weighting, regardless of the prices of the tariff.
+    fn off_peak_score(&self) -> f64 {
+        let weighted =
+            self.periods[Period::P3.index()].kwh() + self.periods[Period::P2.index()].kwh() / 2.0;
+        100.0 * weighted / self.total.kwh()
+    }
+}
+
+impl fmt::Display for Kpis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Consumption indicators:")?;
+        if self.total == Energy(0) {
+            return writeln!(f, "  there is no consumption");
+        }
+
+        let share = |kwh: f64| 100.0 * kwh / self.total.kwh();
+        let valley = share(self.periods[Period::P3.index()].kwh());
+        writeln!(f, "  valley (P3) share: {valley:.1} %")?;
+        writeln!(f, "  weekend share: {:.1} %", share(self.weekend.kwh()))?;
+        writeln!(f, "  base load share: {:.1} %", share(self.base_load()))?;
+        writeln!(f, "  off-peak score: {:.0}/100", self.off_peak_score())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(day: u8, hour: u16, kwh: &str) -> Reading {
+        Reading {
+            date: Date::new(2023, 1, day).unwrap(),
+            minute: hour * 60,
+            minutes: 60,
+            energy: Energy::parse_kwh(kwh).unwrap(),
+            estimated: false,
+            repeated: false,
+        }
+    }
+
+    #[test]
+    fn indicators() {
+        let mut kpis = Kpis::default();
+        // Friday 6 and Saturday 7, whose readings are out of order.
+        kpis.add(&reading(6, 0, "0.5"), Period::P3, DayType::Workday);
+        kpis.add(&reading(7, 0, "1.0"), Period::P3, DayType::Weekend);
+        kpis.add(&reading(6, 12, "2.5"), Period::P1, DayType::Workday);
+        kpis.add(&reading(7, 12, "0.5"), Period::P3, DayType::Weekend);
+        kpis.add(&reading(6, 20, "1.5"), Period::P2, DayType::Workday);
+
+        // The base load is 3 * 0.5 of the Friday and 2 * 0.5 of the Saturday, of 6 kWh.
+        assert_eq!(
+            kpis.to_string(),
+            "Consumption indicators:\n\
+             \x20 valley (P3) share: 33.3 %\n\
+             \x20 weekend share: 25.0 %\n\
+             \x20 base load share: 41.7 %\n\
+             \x20 off-peak score: 46/100\n"
+        );
+    }
+
+    #[test]
+    fn no_consumption() {
+        assert_eq!(
+            Kpis::default().to_string(),
+            "Consumption indicators:\n  there is no consumption\n"
+        );
+    }
+}
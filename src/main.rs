@@ -1,3 +1,245 @@
-fn main() {
-    println!("Hello, world!");
+mod calendar;
+mod cmd;
+mod compressed;
+mod date;
+mod energy;
+mod error;
+mod inflate;
+mod kpi;
+mod period;
+mod quality;
+mod reader;
+mod report;
+mod timezone;
+mod utils;
+
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use calendar::DayType;
+use cmd::{Cmd, Summary};
+use error::Error;
+use period::Period;
+use reader::Format;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        print!("{}", cmd::USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            match e {
+                Error::Args(_) => {
+                    eprintln!("\nFor more information, try '--help'.");
+                    ExitCode::FAILURE
+                }
+                Error::DataQuality => ExitCode::from(cmd::DATA_QUALITY_EXIT_CODE),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), Error> {
+    let cmd = Cmd::from_args(args)?;
+    if cmd.print_effective_windows {
+        print_effective_windows(&cmd);
+        return Ok(());
+    }
+
+    let mut file_quality = Vec::new();
+    let readings: reader::Readings = match cmd.csv_filepaths.as_slice() {
+        [path] => {
+            let (readings, detected) = reader::readings(path, &cmd.reader_options)?;
+            if let Some(format) = detected {
+                note_detected_format(path, format);
+            }
+            readings
+        }
+        paths => {
+            let merged = reader::merge(paths, &cmd.reader_options)?;
+            for (path, format) in &merged.detected {
+                note_detected_format(path, *format);
+            }
+            if merged.duplicates > 0 {
+                eprintln!(
+                    "warning: {} readings ignored because another file already had their hour",
+                    merged.duplicates
+                );
+            }
+            file_quality = merged.quality;
+            Box::new(merged.readings.into_iter().map(Ok))
+        }
+    };
+    let mut summary = cmd.run(readings, timezone::now())?;
+    // The merged readings are sorted and unique, so only the files tell their order issues.
+    for q in file_quality {
+        summary.quality.add_file_issues(q);
+    }
+    let (future, estimated, quality) = (summary.future, summary.estimated, &summary.quality);
+    if future > 0 && !cmd.include_future {
+        eprintln!(
+            "warning: {future} readings after the current time excluded, --include-future \
+             includes them"
+        );
+    }
+
+    if estimated > 0 && cmd.fail_on_estimated {
+        return Err(Error::EstimatedReadings(estimated));
+    }
+    if quality.has_issues() && cmd.fail_on_quality_issues {
+        eprint!("{quality}");
+        return Err(Error::DataQuality);
+    }
+
+    if let Some(fields) = &cmd.select {
+        let days = summary.daily.into_days();
+        let days = report::query(&days, &cmd.filters, &cmd.sort);
+        match report::write_csv(&mut io::stdout().lock(), fields, days) {
+            // The reader of the output, e.g. head, doesn't want more rows.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            r => r.map_err(Error::Output)?,
+        }
+        if quality.has_issues() {
+            eprint!("\n{quality}");
+        }
+
+        return Ok(());
+    }
+
+    match write_totals(&mut io::stdout().lock(), &cmd, &summary) {
+        // The reader of the output, e.g. head, doesn't want more lines.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        r => r.map_err(Error::Output),
+    }
+}
+
+fn note_detected_format(path: &Path, format: Format) {
+    let name = match path.to_str() {
+        Some("-") => "the standard input".into(),
+        _ => path.display().to_string(),
+    };
+    eprintln!("note: detected the {} format in {name}", format.name());
+}
+
+/// Writes the consumption and the final counter of each period, followed by the KPIs if the
+/// command asks for them and the data quality issues, if any.
+fn write_totals(out: &mut impl Write, cmd: &Cmd, summary: &Summary) -> io::Result<()> {
+    for p in Period::ALL {
+        let i = p.index();
+        let (base, consumed, counter) = (
+            cmd.base_counters[i],
+            summary.consumed[i],
+            summary.counters[i],
+        );
+        writeln!(
+            out,
+            "{p}: base counter {base}, {consumed} kWh consumed, final counter {counter}"
+        )?;
+    }
+    let (count, estimated) = (summary.readings, summary.estimated);
+    if let Some((first, last)) = summary.dates {
+        writeln!(out, "{count} readings from {first} to {last}")?;
+    }
+
+    if estimated > 0 {
+        let excluded = if cmd.exclude_estimated {
+            ", excluded from the consumption"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "{estimated} of {count} readings are estimated{excluded}"
+        )?;
+    }
+    if cmd.print_kpis {
+        write!(out, "\n{}", summary.kpis)?;
+    }
+    if summary.quality.has_issues() {
+        write!(out, "\n{}", summary.quality)?;
+    }
+
+    Ok(())
+}
+
+/// Prints a timeline of the day for each day type, with the number of the period of each hour;
+/// hours split between several periods are marked with `*`.
+fn print_effective_windows(cmd: &Cmd) {
+    const LABEL_WIDTH: usize = 14;
+
+    println!("{:LABEL_WIDTH$}0         1         2", "");
+    println!("{:LABEL_WIDTH$}012345678901234567890123", "");
+    let mut split_hours = false;
+    for day_type in DayType::ALL {
+        let timeline: String = (0..24)
+            .map(|h| {
+                let period = cmd.period_of(day_type, h * 60);
+                if (h * 60..(h + 1) * 60).any(|m| cmd.period_of(day_type, m) != period) {
+                    split_hours = true;
+                    return '*';
+                }
+
+                match period {
+                    Period::P1 => '1',
+                    Period::P2 => '2',
+                    Period::P3 => '3',
+                }
+            })
+            .collect();
+        println!("{:LABEL_WIDTH$}{timeline}", day_type.name());
+    }
+
+    if split_hours {
+        println!("\n* the hour is split between several periods");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use date::Date;
+    use energy::Energy;
+    use reader::Reading;
+
+    #[test]
+    fn totals() {
+        let args = [
+            "--base-counters=p1=1234.567,p2=2345,p3=3456",
+            "consumption.csv",
+        ];
+        let cmd = Cmd::from_args(args.map(String::from)).unwrap();
+        let day = Date::new(2023, 1, 2).unwrap();
+        let readings = (0..24).map(|h| {
+            Ok(Reading {
+                date: day,
+                minute: h * 60,
+                minutes: 60,
+                energy: Energy(250_000),
+                estimated: h == 0,
+                repeated: false,
+            })
+        });
+        let summary = cmd
+            .run(readings, (Date::new(2023, 2, 1).unwrap(), 0))
+            .unwrap();
+
+        let mut out = Vec::new();
+        write_totals(&mut out, &cmd, &summary).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "P1: base counter 1234.567, 2.000 kWh consumed, final counter 1236.567\n\
+             P2: base counter 2345.000, 2.000 kWh consumed, final counter 2347.000\n\
+             P3: base counter 3456.000, 2.000 kWh consumed, final counter 3458.000\n\
+             24 readings from 2023-01-02 to 2023-01-02\n\
+             1 of 24 readings are estimated\n"
+        );
+    }
 }
@@ -0,0 +1,212 @@
+use std::fmt;
+
+/// The time periods in which the meter splits the consumption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    P1,
+    P2,
+    P3,
+}
+
+impl Period {
+    pub const ALL: [Period; 3] = [Period::P1, Period::P2, Period::P3];
+
+    /// Position of the period in arrays indexed by period.
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Parses the period identifier (e.g. `p1`), case insensitive.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "p1" => Some(Period::P1),
+            "p2" => Some(Period::P2),
+            "p3" => Some(Period::P3),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Period::P1 => "P1",
+            Period::P2 => "P2",
+            Period::P3 => "P3",
+        };
+
+        f.write_str(s)
+    }
+}
+
+/// Minutes in a day.
+pub const DAY_MINUTES: u16 = 24 * 60;
+
+/// A range of minutes of the day `[start, end)`; an `end` lower or equal than `start` means that
+/// the window crosses midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Window {
+    /// Iterates over the minutes of the day covered by the window.
+    pub fn minutes(self) -> impl Iterator<Item = u16> {
+        let len = if self.end > self.start {
+            self.end - self.start
+        } else {
+            DAY_MINUTES - self.start + self.end
+        };
+
+        (0..len).map(move |i| (self.start + i) % DAY_MINUTES)
+    }
+}
+
+/// Maps each minute of the day to its period.
+#[derive(Debug, Clone)]
+pub struct DayIndex {
+    periods: Vec<Period>,
+    /// Minutes that belong to a window that started the day before.
+    carried: Vec<bool>,
+}
+
+impl DayIndex {
+    /// Builds the index from the windows of each period, which must cover every minute of the day
+    /// exactly once.
+    pub fn new(windows: [&[Window]; 3]) -> Result<Self, String> {
+        let mut index = vec![None; DAY_MINUTES as usize];
+        let mut carried = vec![false; DAY_MINUTES as usize];
+        for (period, windows) in Period::ALL.into_iter().zip(windows) {
+            for w in windows {
+                for m in w.minutes() {
+                    carried[m as usize] = m < w.start;
+                    if let Some(other) = index[m as usize] {
+                        return Err(format!(
+                            "{} is assigned to {other} and {period} at the same time",
+                            fmt_minute(m)
+                        ));
+                    }
+
+                    index[m as usize] = Some(period);
+                }
+            }
+        }
+
+        let periods = index
+            .into_iter()
+            .enumerate()
+            .map(|(m, p)| {
+                p.ok_or_else(|| format!("{} isn't assigned to any period", fmt_minute(m as u16)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(DayIndex { periods, carried })
+    }
+
+    /// Returns the period of `minute`, which is in the range `0..DAY_MINUTES`.
+    pub fn get(&self, minute: u16) -> Period {
+        self.periods[minute as usize]
+    }
+
+    /// Returns `true` when every period starts and ends at an o'clock.
+    pub fn is_hour_aligned(&self) -> bool {
+        (0..DAY_MINUTES)
+            .filter(|m| !m.is_multiple_of(60))
+            .all(|m| self.periods[m as usize] == self.periods[m as usize - 1])
+    }
+
+    /// Returns `true` when `minute` belongs to a window that crosses midnight and `minute` is
+    /// after it, hence the window started the day before.
+    pub fn starts_previous_day(&self, minute: u16) -> bool {
+        self.carried[minute as usize]
+    }
+}
+
+/// Formats a minute of the day as `HH:MM`.
+pub fn fmt_minute(minute: u16) -> String {
+    format!("{:02}:{:02}", minute / 60, minute % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::parse_windows;
+
+    fn day_index(p1: &str, p2: &str, p3: &str) -> DayIndex {
+        let [p1, p2, p3] = [p1, p2, p3].map(|w| parse_windows(w).unwrap());
+        DayIndex::new([&p1, &p2, &p3]).unwrap()
+    }
+
+    fn minute(h: u16, m: u16) -> u16 {
+        h * 60 + m
+    }
+
+    #[test]
+    fn day_index_boundaries() {
+        let index = day_index("10-14,18-22", "8-10,14-18,22-0", "0-8");
+
+        // Minutes around each limit, and the start of the hourly and quarter-hourly readings
+        // before it, which are classified by the minute when they start.
+        let cases = [
+            (minute(7, 59), Period::P3),
+            (minute(7, 45), Period::P3),
+            (minute(7, 0), Period::P3),
+            (minute(8, 0), Period::P2),
+            (minute(13, 59), Period::P1),
+            (minute(13, 45), Period::P1),
+            (minute(13, 0), Period::P1),
+            (minute(14, 0), Period::P2),
+            (minute(23, 59), Period::P2),
+            (minute(23, 45), Period::P2),
+            (minute(23, 0), Period::P2),
+            (minute(0, 0), Period::P3),
+        ];
+        for (m, period) in cases {
+            assert_eq!(index.get(m), period, "minute {m}");
+        }
+    }
+
+    #[test]
+    fn day_index_boundaries_of_minute_windows() {
+        let index = day_index("10-14:30,18-22", "8:15-10,14:30-18,22-0:15", "0:15-8:15");
+
+        assert_eq!(index.get(minute(8, 14)), Period::P3);
+        assert_eq!(index.get(minute(8, 15)), Period::P2);
+        assert_eq!(index.get(minute(14, 29)), Period::P1);
+        assert_eq!(index.get(minute(14, 30)), Period::P2);
+        assert_eq!(index.get(minute(0, 14)), Period::P2);
+        assert_eq!(index.get(minute(0, 15)), Period::P3);
+
+        // Quarter-hourly readings.
+        assert_eq!(index.get(minute(8, 0)), Period::P3);
+        assert_eq!(index.get(minute(8, 15)), Period::P2);
+        assert_eq!(index.get(minute(14, 15)), Period::P1);
+        assert_eq!(index.get(minute(14, 30)), Period::P2);
+        assert_eq!(index.get(minute(0, 0)), Period::P2);
+        assert_eq!(index.get(minute(0, 15)), Period::P3);
+
+        // Hourly readings split between two periods are classified by the minute when they
+        // start.
+        assert_eq!(index.get(minute(9, 0)), Period::P2);
+        assert_eq!(index.get(minute(14, 0)), Period::P1);
+        assert_eq!(index.get(minute(15, 0)), Period::P2);
+    }
+
+    #[test]
+    fn day_index_hour_alignment() {
+        assert!(day_index("10-14,18-22", "8-10,14-18,22-0", "0-8").is_hour_aligned());
+        assert!(!day_index("10:30-14,18-22", "8-10:30,14-18,22-0", "0-8").is_hour_aligned());
+    }
+
+    #[test]
+    fn day_index_window_crossing_midnight() {
+        let index = day_index("10-14,18-22", "8-10,14-18,22-2", "2-8");
+
+        assert!(!index.starts_previous_day(minute(23, 0)));
+        assert!(index.starts_previous_day(minute(1, 59)));
+        assert!(!index.starts_previous_day(minute(2, 0)));
+        assert_eq!(index.get(minute(1, 59)), Period::P2);
+        assert_eq!(index.get(minute(2, 0)), Period::P3);
+    }
+}
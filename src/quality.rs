@@ -0,0 +1,205 @@
+//! Detection of gaps, duplicates and out-of-order rows in the series of readings.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::period::fmt_minute;
+use crate::reader::Reading;
+use crate::timezone::{local_to_utc, to_local};
+
+/// Maximum number of ranges listed for each kind of issue in the report.
+const MAX_LISTED: usize = 10;
+
+/// Checks the readings in the order they are read; the duplicates, which start at the same time
+/// as a previous reading, are reported and ignored.
+///
+/// Timestamps are minutes since 1970-01-01 00:00 UTC, so the readings of the days of the clock
+/// changes are contiguous.
+#[derive(Debug, Default)]
+pub struct Quality {
+    /// Start and end of the last reading.
+    prev: Option<(i64, i64)>,
+    /// Start of every checked reading.
+    seen: HashSet<i64>,
+    /// Start and end of the missing intervals.
+    gaps: Vec<(i64, i64)>,
+    /// Start of the readings with the same start as a previous one.
+    duplicates: Vec<i64>,
+    /// Start of the readings starting before the previous one.
+    out_of_order: Vec<i64>,
+}
+
+impl Quality {
+    /// Checks the next reading; returns `false` if it's a duplicate, which must be ignored.
+    pub fn check(&mut self, r: &Reading) -> bool {
+        let start = local_to_utc(r.date, r.minute, r.repeated);
+        let end = start + i64::from(r.minutes);
+
+        if !self.seen.insert(start) {
+            self.duplicates.push(start);
+            return false;
+        }
+        if let Some((prev_start, prev_end)) = self.prev {
+            if start < prev_start {
+                self.out_of_order.push(start);
+                return true;
+            }
+            if start > prev_end {
+                self.gaps.push((prev_end, start));
+            }
+        }
+
+        self.prev = Some((start, end));
+        true
+    }
+
+    /// Adds the duplicates and out-of-order readings of `file`, the check of one of several
+    /// merged files; its gaps are left out because other files may cover them.
+    pub fn add_file_issues(&mut self, file: Quality) {
+        self.duplicates.extend(file.duplicates);
+        self.out_of_order.extend(file.out_of_order);
+    }
+
+    pub fn has_issues(&self) -> bool {
+        !(self.gaps.is_empty() && self.duplicates.is_empty() && self.out_of_order.is_empty())
+    }
+}
+
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Data quality issues:")?;
+        if !self.gaps.is_empty() {
+            let missing: i64 = self.gaps.iter().map(|(s, e)| e - s).sum();
+            let ranges = self
+                .gaps
+                .iter()
+                .map(|&(s, e)| format!("{} to {}", fmt_ts(s), fmt_ts(e)));
+            writeln!(
+                f,
+                "  gaps ({}, {} missing): {}",
+                self.gaps.len(),
+                fmt_duration(missing),
+                list(ranges, self.gaps.len())
+            )?;
+        }
+        if !self.duplicates.is_empty() {
+            writeln!(
+                f,
+                "  duplicated readings ({}): {}",
+                self.duplicates.len(),
+                list(
+                    self.duplicates.iter().map(|&t| fmt_ts(t)),
+                    self.duplicates.len()
+                )
+            )?;
+        }
+        if !self.out_of_order.is_empty() {
+            writeln!(
+                f,
+                "  out-of-order readings ({}): {}",
+                self.out_of_order.len(),
+                list(
+                    self.out_of_order.iter().map(|&t| fmt_ts(t)),
+                    self.out_of_order.len()
+                )
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Joins the first [`MAX_LISTED`] items, noting how many are left out of `len`.
+fn list(items: impl Iterator<Item = String>, len: usize) -> String {
+    let mut s = items.take(MAX_LISTED).collect::<Vec<_>>().join(", ");
+    if len > MAX_LISTED {
+        s.push_str(&format!(" and {} more", len - MAX_LISTED));
+    }
+
+    s
+}
+
+/// Formats a timestamp as the local `YYYY-MM-DD HH:MM`.
+fn fmt_ts(ts: i64) -> String {
+    let (date, minute) = to_local(ts);
+    format!("{date} {}", fmt_minute(minute))
+}
+
+fn fmt_duration(minutes: i64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (1, 0) => "1 hour".to_string(),
+        (h, 0) => format!("{h} hours"),
+        (0, m) => format!("{m} minutes"),
+        (h, m) => format!("{h}h {m}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::Date;
+    use crate::energy::Energy;
+
+    fn reading(hour: u16) -> Reading {
+        Reading {
+            date: Date::new(2023, 1, 2).unwrap(),
+            minute: hour * 60,
+            minutes: 60,
+            energy: Energy(100_000),
+            estimated: false,
+            repeated: false,
+        }
+    }
+
+    #[test]
+    fn duplicates_are_ignored() {
+        let mut quality = Quality::default();
+        let checked: Vec<bool> = [0, 1, 1, 3, 2, 0]
+            .map(|h| quality.check(&reading(h)))
+            .into();
+
+        assert_eq!(checked, [true, true, false, true, true, false]);
+        assert_eq!(quality.duplicates.len(), 2);
+        assert_eq!(quality.out_of_order.len(), 1);
+        assert_eq!(quality.gaps.len(), 1);
+    }
+
+    #[test]
+    fn file_issues_without_gaps() {
+        let mut file = Quality::default();
+        for h in [0, 0, 2, 1] {
+            file.check(&reading(h));
+        }
+
+        let mut quality = Quality::default();
+        quality.add_file_issues(file);
+        assert_eq!(quality.duplicates.len(), 1);
+        assert_eq!(quality.out_of_order.len(), 1);
+        assert!(quality.gaps.is_empty());
+    }
+
+    #[test]
+    fn report() {
+        let mut quality = Quality::default();
+        assert!(!quality.has_issues());
+        for h in [0, 3, 3, 6, 1] {
+            quality.check(&reading(h));
+        }
+
+        assert!(quality.has_issues());
+        assert_eq!(
+            quality.to_string(),
+            "Data quality issues:\n\
+             \x20 gaps (2, 4 hours missing): 2023-01-02 01:00 to 2023-01-02 03:00, \
+             2023-01-02 04:00 to 2023-01-02 06:00\n\
+             \x20 duplicated readings (1): 2023-01-02 03:00\n\
+             \x20 out-of-order readings (1): 2023-01-02 01:00\n"
+        );
+        assert_eq!(fmt_duration(15), "15 minutes");
+        assert_eq!(fmt_duration(75), "1h 15m");
+        assert_eq!(
+            list((0..12).map(|i| i.to_string()), 12),
+            "0, 1, 2, 3, 4, 5, 6, 7, 8, 9 and 2 more"
+        );
+    }
+}
@@ -0,0 +1,1086 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+use super::format::{parse_method, parse_timestamp, Format, Layout};
+use super::{Options, Reading};
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::error::Error;
+use crate::period::{fmt_minute, DAY_MINUTES};
+use crate::timezone::{clock_change, ClockChange};
+
+/// Delimiters that are detected from the header row.
+const DELIMITERS: [char; 3] = [',', ';', '\t'];
+
+/// Streams the readings of a CSV file of one of the supported [`Format`]s, row by row.
+///
+/// The file must have a header row naming its columns; they are located by the names that the
+/// format defines, ignoring case, accents and the difference between spaces and underscores, so
+/// `Método de obtención` matches `Metodo de obtencion`. The method column is optional. The fields
+/// can be quoted as RFC 4180 defines (see [`split_fields`]).
+///
+/// The time column, which can be the date column too, is the time when the reading ends, either
+/// as the hour number for hourly readings or as `HH:MM` for hourly or quarter-hourly ones; the
+/// resolution of the latter is detected from the first rows.
+///
+/// The hour numbers count the hours of the day, so the day when the clocks go forward goes from
+/// 1 to 23, skipping 02:00-03:00, and the day when they go back goes from 1 to 25, being 3 and 4
+/// the two 02:00-03:00 hours. With `HH:MM`, the rows of the latter day ending again between
+/// 02:00 and 03:00 are the repeated hour.
+///
+/// With a column map, the columns are the mapped ones, matched the same way, and the timestamp
+/// column is the date and time when the reading starts, parsed with the map's pattern.
+///
+/// Unless the options set them, the delimiter is detected from the header row and the decimal
+/// separator from the first consumption value that tells it apart from the thousands separator
+/// (see [`decimal_separator_of`]); the other one of `.` and `,` is taken as the thousands
+/// separator. The rows before that value are kept until it's found, and if there is none, the
+/// separator is `.` in the `,` separated files, whose values can't have a thousands `,` unless
+/// they are quoted, and an ambiguous value like `1.234` is an error in the rest.
+pub struct Reader<R> {
+    input: R,
+    format: Format,
+    /// Pattern of the timestamp column when the columns are mapped.
+    timestamp_format: Option<String>,
+    mwh_per_unit: u64,
+    buf: Vec<u8>,
+    line: String,
+    line_num: usize,
+    columns: Columns,
+    delimiter: char,
+    decimal_separator: Option<char>,
+    /// Line numbers and lines of the rows read before the decimal separator is known.
+    unsettled: VecDeque<(usize, String)>,
+    /// Minutes of each reading, once detected.
+    resolution: Option<u16>,
+    /// Row read in advance to detect the resolution.
+    pending: Option<Result<Row, Error>>,
+    /// Date, end and whether it's repeated of the last `HH:MM` row, to detect the repeated hour.
+    last_end: Option<(Date, u16, bool)>,
+}
+
+/// A parsed CSV row.
+struct Row {
+    line_num: usize,
+    date: Date,
+    /// Minute of the day when the reading ends (1-1440), or starts (0-1439) if `starts` is set;
+    /// hour numbers are taken as ending at their o'clock, up to 25:00.
+    time: u16,
+    starts: bool,
+    /// Whether the time has the `HH:MM` format rather than being the hour number.
+    with_minutes: bool,
+    energy: Energy,
+    estimated: bool,
+}
+
+/// Positions of the used columns.
+struct Columns {
+    header: Vec<String>,
+    date: usize,
+    hour: usize,
+    kwh: usize,
+    method: Option<usize>,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Creates a reader of a file of `format`, consuming the header row of `input`; the format is
+    /// ignored if the options have a column map.
+    pub fn new(input: R, format: Format, options: &Options) -> Result<Self, Error> {
+        let mut reader = Reader {
+            input,
+            format,
+            timestamp_format: options.column_map.as_ref().map(|m| m.format.clone()),
+            mwh_per_unit: match options.column_map {
+                Some(_) => Energy::KWH,
+                None => layout(format).mwh_per_unit,
+            },
+            buf: Vec::new(),
+            line: String::new(),
+            line_num: 0,
+            columns: Columns {
+                header: Vec::new(),
+                date: 0,
+                hour: 0,
+                kwh: 0,
+                method: None,
+            },
+            delimiter: ',',
+            decimal_separator: options.decimal_separator,
+            unsettled: VecDeque::new(),
+            resolution: None,
+            pending: None,
+            last_end: None,
+        };
+
+        if !reader.next_line()? {
+            return Err(Error::csv(1, "there is no data, not even the header row"));
+        }
+
+        reader.delimiter = options
+            .delimiter
+            .unwrap_or_else(|| sniff_delimiter(&reader.line));
+
+        let line_num = reader.line_num;
+        let header = split_fields(&reader.line, reader.delimiter)
+            .ok_or_else(|| Error::csv(line_num, "a quoted field isn't closed"))?;
+        let normalized: Vec<String> = header.iter().map(|h| normalize_column_name(h)).collect();
+        let find = |names: &[&str]| find_column(&normalized, names);
+        reader.columns = match &options.column_map {
+            Some(map) => {
+                let column = |name: &str| {
+                    find(&[name]).ok_or_else(|| {
+                        Error::csv(
+                            line_num,
+                            format!("mapped column {name} not found in the header"),
+                        )
+                    })
+                };
+                let timestamp = column(&map.timestamp)?;
+                Columns {
+                    date: timestamp,
+                    hour: timestamp,
+                    kwh: column(&map.value)?,
+                    method: map.method.as_deref().map(column).transpose()?,
+                    header: header.into_iter().map(Cow::into_owned).collect(),
+                }
+            }
+            None => {
+                let layout = layout(reader.format);
+                let column = |names: &[&str]| {
+                    find(names).ok_or_else(|| {
+                        Error::csv(
+                            line_num,
+                            format!(
+                                "column {} not found in the header of the {} format",
+                                names.join(" or "),
+                                reader.format.name(),
+                            ),
+                        )
+                    })
+                };
+                Columns {
+                    date: column(layout.date)?,
+                    hour: column(layout.time)?,
+                    kwh: column(layout.kwh)?,
+                    method: find(layout.method),
+                    header: header.into_iter().map(Cow::into_owned).collect(),
+                }
+            }
+        };
+
+        Ok(reader)
+    }
+
+    /// Reads the next non-blank line into `self.line`; returns `false` at the end of the input.
+    ///
+    /// Lines are decoded as UTF-8, or as Windows-1252 when they aren't valid UTF-8, which is how
+    /// some distributor portals export files; a leading UTF-8 BOM is skipped.
+    fn next_line(&mut self) -> Result<bool, Error> {
+        loop {
+            self.buf.clear();
+            let n = self
+                .input
+                .read_until(b'\n', &mut self.buf)
+                .map_err(|e| Error::csv(self.line_num + 1, e.to_string()))?;
+            if n == 0 {
+                return Ok(false);
+            }
+
+            self.line_num += 1;
+            let mut bytes = self.buf.as_slice();
+            if self.line_num == 1 {
+                bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+            }
+
+            self.line.clear();
+            match std::str::from_utf8(bytes) {
+                Ok(s) => self.line.push_str(s),
+                Err(_) => self
+                    .line
+                    .extend(bytes.iter().map(|&b| decode_windows_1252(b))),
+            }
+
+            if !self.line.trim().is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Parses the row at `line`, whose number is `n`, once the decimal separator is known.
+    fn parse_line(&self, line: &str, n: usize) -> Result<Row, Error> {
+        let fields = split_fields(line, self.delimiter)
+            .ok_or_else(|| Error::csv(n, "a quoted field isn't closed"))?;
+        let columns = &self.columns;
+        let field = |col: usize| {
+            fields
+                .get(col)
+                .map(|f| f.as_ref())
+                .ok_or_else(|| Error::csv(n, format!("missing column {}", columns.header[col])))
+        };
+
+        let (date, time, with_minutes, starts) = match &self.timestamp_format {
+            Some(pattern) => {
+                let timestamp = field(columns.date)?;
+                let (date, minute) = parse_timestamp(pattern, timestamp)
+                    .ok_or_else(|| Error::csv(n, format!("invalid timestamp '{timestamp}'")))?;
+                (date, minute, pattern.contains("%M"), true)
+            }
+            None => {
+                let (date, hour) = if columns.date == columns.hour {
+                    let date_time = field(columns.date)?;
+                    date_time
+                        .split_once(' ')
+                        .map_or((date_time, ""), |(d, t)| (d, t.trim()))
+                } else {
+                    (field(columns.date)?, field(columns.hour)?)
+                };
+                let date = (layout(self.format).parse_date)(date)
+                    .ok_or_else(|| Error::csv(n, format!("invalid date '{date}'")))?;
+                (
+                    date,
+                    parse_end(hour)
+                        .ok_or_else(|| Error::csv(n, format!("invalid hour '{hour}'")))?,
+                    hour.contains(':'),
+                    false,
+                )
+            }
+        };
+        let kwh = field(columns.kwh)?;
+        let decimal = self
+            .decimal_separator
+            .expect("the decimal separator is settled before parsing the rows");
+        let energy = parse_energy(kwh, decimal, self.mwh_per_unit)
+            .ok_or_else(|| Error::csv(n, format!("invalid consumption '{kwh}'")))?;
+
+        let estimated = match columns.method {
+            None => false,
+            Some(col) => {
+                let m = field(col)?;
+                parse_method(m)
+                    .ok_or_else(|| Error::csv(n, format!("invalid reading method '{m}'")))?
+            }
+        };
+
+        Ok(Row {
+            line_num: n,
+            date,
+            time,
+            starts,
+            with_minutes,
+            energy,
+            estimated,
+        })
+    }
+
+    fn next_row(&mut self) -> Option<Result<Row, Error>> {
+        if let Some(row) = self.pending.take() {
+            return Some(row);
+        }
+
+        if let Some((n, line)) = self.unsettled.pop_front() {
+            return Some(self.parse_line(&line, n));
+        }
+
+        match self.next_line() {
+            Ok(true) => {}
+            Ok(false) => return None,
+            Err(e) => return Some(Err(e)),
+        }
+        if self.decimal_separator.is_none() {
+            if let Err(e) = self.settle_decimal_separator() {
+                return Some(Err(e));
+            }
+            if let Some((n, line)) = self.unsettled.pop_front() {
+                return Some(self.parse_line(&line, n));
+            }
+        }
+
+        Some(self.parse_line(&self.line, self.line_num))
+    }
+
+    /// Reads rows from the current line until a consumption value tells the decimal separator,
+    /// keeping them in `unsettled`, or up to the end of the input.
+    fn settle_decimal_separator(&mut self) -> Result<(), Error> {
+        let mut ambiguous = None;
+        loop {
+            let value = split_fields(&self.line, self.delimiter)
+                .and_then(|fields| fields.get(self.columns.kwh).map(|f| f.to_string()));
+            self.unsettled
+                .push_back((self.line_num, std::mem::take(&mut self.line)));
+            if let Some(value) = value {
+                if let Some(separator) = decimal_separator_of(&value) {
+                    self.decimal_separator = Some(separator);
+                    return Ok(());
+                }
+                if ambiguous.is_none() && value.contains(['.', ',']) {
+                    ambiguous = Some((self.line_num, value));
+                }
+            }
+
+            if !self.next_line()? {
+                break;
+            }
+        }
+
+        match ambiguous {
+            Some((n, value)) if self.delimiter != ',' => Err(Error::csv(
+                n,
+                format!(
+                    "the decimal separator of '{value}' is ambiguous, and no other value tells \
+                     it; set it with --input-decimal-separator"
+                ),
+            )),
+            _ => {
+                self.decimal_separator = Some('.');
+                Ok(())
+            }
+        }
+    }
+
+    /// Detects the resolution from the first row, reading the next one in advance when the
+    /// first row doesn't tell it.
+    fn detect_resolution(&mut self, first: &Row) -> u16 {
+        if !first.with_minutes {
+            return 60;
+        }
+        if !first.time.is_multiple_of(60) {
+            return 15;
+        }
+
+        let Some(next) = self.next_row() else {
+            return 60;
+        };
+        let resolution = match &next {
+            Ok(r) if r.date == first.date && r.time == first.time + 15 => 15,
+            _ => 60,
+        };
+
+        self.pending = Some(next);
+        resolution
+    }
+
+    /// Returns the minute of the day when the reading of `row` starts and whether it's in the
+    /// hour repeated when the clocks go back.
+    fn start(&mut self, row: &Row, resolution: u16) -> Result<(u16, bool), Error> {
+        if row.starts {
+            return Ok((row.time, false));
+        }
+
+        let change = clock_change(row.date);
+        if row.with_minutes {
+            let in_repeated_hour =
+                change == Some(ClockChange::Back) && (121..=180).contains(&row.time);
+            let repeated = in_repeated_hour
+                && matches!(self.last_end, Some((date, end, repeated))
+                    if date == row.date && (repeated || end >= row.time));
+            self.last_end = Some((row.date, row.time, repeated));
+            return Ok((row.time - resolution, repeated));
+        }
+
+        let hour = row.time / 60;
+        let hours = match change {
+            Some(ClockChange::Forward) => 23,
+            Some(ClockChange::Back) => 25,
+            None => 24,
+        };
+        if hour > hours {
+            return Err(Error::csv(
+                row.line_num,
+                format!(
+                    "hour {hour} doesn't exist on {}, which has {hours} hours",
+                    row.date
+                ),
+            ));
+        }
+
+        Ok(match change {
+            Some(ClockChange::Forward) if hour >= 3 => (hour * 60, false),
+            Some(ClockChange::Back) if hour >= 4 => ((hour - 2) * 60, hour == 4),
+            _ => ((hour - 1) * 60, false),
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = Result<Reading, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let row = match self.next_row()? {
+            Ok(row) => row,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let resolution = match self.resolution {
+            Some(r) => r,
+            None => {
+                let r = self.detect_resolution(&row);
+                self.resolution = Some(r);
+                r
+            }
+        };
+        if !row.time.is_multiple_of(resolution) {
+            return Some(Err(Error::csv(
+                row.line_num,
+                format!(
+                    "{} isn't aligned with the {resolution}-minute readings of the file",
+                    fmt_minute(row.time)
+                ),
+            )));
+        }
+
+        let (minute, repeated) = match self.start(&row, resolution) {
+            Ok(start) => start,
+            Err(e) => return Some(Err(e)),
+        };
+
+        Some(Ok(Reading {
+            date: row.date,
+            minute,
+            minutes: resolution,
+            energy: row.energy,
+            estimated: row.estimated,
+            repeated,
+        }))
+    }
+}
+
+/// Parses the time when a reading ends, either the hour number (1-25) or a `HH:MM` time, into the
+/// minute of the day (1-1440); the hour numbers end at their o'clock.
+fn parse_end(hour: &str) -> Option<u16> {
+    match hour.split_once(':') {
+        None => hour
+            .parse::<u16>()
+            .ok()
+            .filter(|h| (1..=25).contains(h))
+            .map(|h| h * 60),
+        Some((h, m)) if m.len() == 2 => match (h.parse::<u16>(), m.parse::<u16>()) {
+            (Ok(h), Ok(m)) if h <= 24 && m < 60 => {
+                Some(h * 60 + m).filter(|end| (1..=DAY_MINUTES).contains(end))
+            }
+            _ => None,
+        },
+        Some(_) => None,
+    }
+}
+
+/// Returns the decimal separator of the number `s` when it tells it apart from the thousands
+/// separator: the last one when it has both, as in `1.234,567`, the other one when it has one of
+/// them several times, as in `1.234.567`, and the one it has when it can't group thousands, as in
+/// `0,123` or `1.5`. `None` for the numbers without separators and the ambiguous ones like
+/// `1.234`.
+fn decimal_separator_of(s: &str) -> Option<char> {
+    let (dots, commas) = (s.matches('.').count(), s.matches(',').count());
+    let only = match (dots, commas) {
+        (0, 0) => return None,
+        (0, 1) => ',',
+        (1, 0) => '.',
+        (0, _) => return Some('.'),
+        (_, 0) => return Some(','),
+        _ => return s.chars().rev().find(|c| matches!(c, '.' | ',')),
+    };
+
+    let (integer, fraction) = s.split_once(only)?;
+    let thousands = (1..=3).contains(&integer.len()) && !integer.starts_with('0');
+    if thousands && fraction.len() == 3 {
+        None
+    } else {
+        Some(only)
+    }
+}
+
+/// Parses an amount of units of `mwh_per_unit` mWh whose decimal separator is `decimal` (`.` or
+/// `,`) and whose integer part can be grouped in thousands with the other one of them (e.g.
+/// `1.234,567` with `,`); only digits and separators are accepted, not signs nor exponents.
+fn parse_energy(s: &str, decimal: char, mwh_per_unit: u64) -> Option<Energy> {
+    if !s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'.' | b',')) {
+        return None;
+    }
+
+    let thousands = if decimal == ',' { '.' } else { ',' };
+    let (integer, fraction) = s.split_once(decimal).unwrap_or((s, ""));
+    let mut groups = integer.split(thousands);
+    let first = groups.next()?;
+    let mut digits = first.to_string();
+    for g in groups {
+        if first.is_empty() || first.len() > 3 || g.len() != 3 {
+            return None;
+        }
+        digits.push_str(g);
+    }
+
+    if !fraction.is_empty() {
+        digits.push('.');
+        digits.push_str(fraction);
+    }
+
+    Energy::parse(&digits, mwh_per_unit)
+}
+
+pub(super) const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Characters of the Windows-1252 bytes from 0x80 to 0x9F; the rest of bytes are the same as in
+/// Latin-1, hence the same as the Unicode code points. The undefined bytes map to U+FFFD.
+const WINDOWS_1252_80_9F: [char; 32] = [
+    '€', '\u{FFFD}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{FFFD}', 'Ž',
+    '\u{FFFD}', '\u{FFFD}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{FFFD}',
+    'ž', 'Ÿ',
+];
+
+fn decode_windows_1252(b: u8) -> char {
+    match b {
+        0x80..=0x9F => WINDOWS_1252_80_9F[(b - 0x80) as usize],
+        _ => char::from(b),
+    }
+}
+
+/// Lowercases `name` and removes its surrounding whitespace and accents, replacing the inner
+/// spaces by underscores.
+fn normalize_column_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c.to_lowercase().next().unwrap_or(c) {
+            'á' | 'à' => 'a',
+            'é' | 'è' => 'e',
+            'í' | 'ì' => 'i',
+            'ó' | 'ò' => 'o',
+            'ú' | 'ù' | 'ü' => 'u',
+            ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Detects the CSV format of the file whose first bytes are `sample`.
+///
+/// The format is the first one, in the order of [`Format::ALL`], whose columns are in the header
+/// row and whose date format matches the first data row, if it's in the sample.
+pub fn detect_format(sample: &[u8], delimiter: Option<char>) -> Option<Format> {
+    let sample = sample.strip_prefix(UTF8_BOM).unwrap_or(sample);
+    // The last line may be truncated, unless it's the only one.
+    let sample = match sample.iter().rposition(|&b| b == b'\n') {
+        Some(end) => &sample[..end],
+        None => sample,
+    };
+    let mut lines = sample
+        .split(|&b| b == b'\n')
+        .map(|l| match std::str::from_utf8(l) {
+            Ok(s) => s.to_string(),
+            Err(_) => l.iter().map(|&b| decode_windows_1252(b)).collect(),
+        })
+        .filter(|l| !l.trim().is_empty());
+    let header = lines.next()?;
+    let row = lines.next();
+
+    let delimiter = delimiter.unwrap_or_else(|| sniff_delimiter(&header));
+    let normalized: Vec<String> = split_fields(&header, delimiter)?
+        .iter()
+        .map(|h| normalize_column_name(h))
+        .collect();
+    let fields = match &row {
+        Some(r) => split_fields(r, delimiter)?,
+        None => Vec::new(),
+    };
+    Format::ALL.into_iter().find(|&format| {
+        let Some(layout) = format.layout() else {
+            return false;
+        };
+        let (Some(date), Some(_), Some(_)) = (
+            find_column(&normalized, layout.date),
+            find_column(&normalized, layout.time),
+            find_column(&normalized, layout.kwh),
+        ) else {
+            return false;
+        };
+
+        match (&row, fields.get(date)) {
+            (Some(_), Some(value)) => {
+                let value = value.split(' ').next().unwrap_or(value);
+                (layout.parse_date)(value).is_some()
+            }
+            _ => true,
+        }
+    })
+}
+
+/// Returns the layout of `format`, which must be a CSV one.
+fn layout(format: Format) -> Layout {
+    format
+        .layout()
+        .unwrap_or_else(|| panic!("{} isn't a CSV format", format.name()))
+}
+
+/// Returns the position of the first of `names` in the `normalized` column names.
+fn find_column(normalized: &[String], names: &[&str]) -> Option<usize> {
+    names.iter().find_map(|name| {
+        let name = normalize_column_name(name);
+        normalized.iter().position(|h| *h == name)
+    })
+}
+
+/// Returns the delimiter among [`DELIMITERS`] most used in `header` out of the quoted fields, the
+/// first one on ties.
+fn sniff_delimiter(header: &str) -> char {
+    let mut quoted = false;
+    let unquoted: String = header
+        .chars()
+        .filter(|&c| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            !quoted
+        })
+        .collect();
+    DELIMITERS
+        .into_iter()
+        .rev()
+        .max_by_key(|d| unquoted.matches(*d).count())
+        .expect("there are delimiters")
+}
+
+/// Splits a CSV `line` into its fields without their surrounding spaces. As RFC 4180 defines, a
+/// field can be quoted to contain the delimiter, and its quotes are then doubled, as in `"1,5"` or
+/// `"the ""P1"" period"`; a quoted field can't span several lines though. `None` if a quoted field
+/// isn't closed or there is something else than spaces between its closing quote and the
+/// delimiter.
+fn split_fields(line: &str, delimiter: char) -> Option<Vec<Cow<'_, str>>> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let mut fields = Vec::new();
+    let mut rest = line;
+    loop {
+        let Some(quoted) = rest.trim_start_matches(' ').strip_prefix('"') else {
+            match rest.split_once(delimiter) {
+                Some((field, next)) => {
+                    fields.push(Cow::Borrowed(field.trim()));
+                    rest = next;
+                    continue;
+                }
+                None => {
+                    fields.push(Cow::Borrowed(rest.trim()));
+                    return Some(fields);
+                }
+            }
+        };
+
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (i, '"') if quoted[i + 1..].starts_with('"') => {
+                    value.push('"');
+                    chars.next();
+                }
+                (i, '"') => break i + 1,
+                (_, c) => value.push(c),
+            }
+        };
+        fields.push(Cow::Owned(value));
+
+        let after = quoted[end..].trim_start_matches(' ');
+        match after.strip_prefix(delimiter) {
+            Some(next) => rest = next,
+            None if after.trim().is_empty() => return Some(fields),
+            None => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Options;
+
+    fn read(csv: &str) -> Result<Vec<Reading>, Error> {
+        read_with(csv, Format::EDistribucion, Options::default())
+    }
+
+    fn read_with(csv: &str, format: Format, options: Options) -> Result<Vec<Reading>, Error> {
+        Reader::new(csv.as_bytes(), format, &options)?.collect()
+    }
+
+    fn kwh(readings: Result<Vec<Reading>, Error>) -> Vec<String> {
+        readings
+            .unwrap()
+            .iter()
+            .map(|r| r.energy.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn rows() {
+        let csv = "CUPS,Fecha,Hora,Consumo_kWh\n\
+                   ES0001,01/01/2023,1,0.125\n\
+                   \n\
+                   ES0001,01/01/2023,24,1.5\n";
+        let readings = read(csv).unwrap();
+        let readings: Vec<_> = readings
+            .iter()
+            .map(|r| (r.date, r.minute, r.energy))
+            .collect();
+        let date = Date::new(2023, 1, 1).unwrap();
+        assert_eq!(
+            readings,
+            [
+                (date, 0, Energy(125_000)),
+                (date, 23 * 60, Energy(1_500_000))
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_rows() {
+        let cases = [
+            ("", "there is no data, not even the header row"),
+            ("Fecha,Hora\n", "column Consumo_kWh not found in the header"),
+            (
+                "Fecha,Hora,Consumo_kWh\n01/01/2023,25,1\n",
+                "hour 25 doesn't exist on 2023-01-01, which has 24 hours",
+            ),
+            (
+                "Fecha,Hora,Consumo_kWh\n01/01/2023,1,-1\n",
+                "invalid consumption '-1'",
+            ),
+            (
+                "Fecha,Hora,Consumo_kWh\n2023-01-01,1,1\n",
+                "invalid date '2023-01-01'",
+            ),
+            (
+                "Fecha,Hora,Consumo_kWh\n01/01/2023,1\n",
+                "missing column Consumo_kWh",
+            ),
+        ];
+        for (csv, error) in cases {
+            let e = read(csv).unwrap_err().to_string();
+            assert!(e.contains(error), "{csv}: {e}");
+        }
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(split_fields("a, b ,c\r\n", ',').unwrap(), ["a", "b", "c"]);
+        assert_eq!(
+            split_fields("\"0,123\",\"x \"\"y\"\"\" ,", ',').unwrap(),
+            ["0,123", "x \"y\"", ""]
+        );
+        assert_eq!(split_fields("\"\",1", ',').unwrap(), ["", "1"]);
+        assert_eq!(split_fields("\"a\"\t\tb", '\t').unwrap(), ["a", "", "b"]);
+        assert_eq!(split_fields("\"0,123,1", ','), None);
+        assert_eq!(split_fields("\"0\"1,2", ','), None);
+        assert_eq!(sniff_delimiter("\"Fecha;Hora\",Consumo,Metodo"), ',');
+    }
+
+    /// Asserts that the only reading of `csv`, a file of `format`, is the first hour of
+    /// 2023-01-02, with 0.5 kWh, and estimated.
+    fn assert_format(csv: &str, format: Format) {
+        let readings = read_with(csv, format, Options::default()).unwrap();
+        assert_eq!(readings.len(), 1, "{}", format.name());
+        let r = readings[0];
+        assert_eq!(
+            (r.date, r.minute, r.minutes),
+            (Date::new(2023, 1, 2).unwrap(), 0, 60)
+        );
+        assert_eq!(r.energy, Energy(500_000));
+        assert!(r.estimated);
+    }
+
+    /// Returns the start minute and whether it's repeated of the readings of `csv`.
+    fn starts(csv: &str, format: Format) -> Vec<(u16, bool)> {
+        read_with(csv, format, Options::default())
+            .unwrap()
+            .iter()
+            .map(|r| (r.minute, r.repeated))
+            .collect()
+    }
+
+    #[test]
+    fn numbered_hours_of_the_clock_changes() {
+        let rows = |date, hours| -> String {
+            let mut csv = String::from("CUPS;Fecha;Hora;Consumo_kWh;Metodo_obtencion\n");
+            for hour in hours {
+                csv += &format!("ES0031;{date};{hour};0,100;R\n");
+            }
+            csv
+        };
+
+        // From 02:00 the clocks go to 03:00, so hour 3 is 03:00-04:00 and hour 23 the last one.
+        let starts_of = |hours| starts(&rows("26/03/2023", hours), Format::EDistribucion);
+        assert_eq!(
+            starts_of(1..=4),
+            [(0, false), (60, false), (180, false), (240, false)]
+        );
+        assert_eq!(starts_of(23..=23), [(23 * 60, false)]);
+        let error = Reader::new(
+            rows("26/03/2023", 24..=24).as_bytes(),
+            Format::EDistribucion,
+            &Options::default(),
+        )
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("hour 24 doesn't exist on 2023-03-26, which has 23 hours"),
+            "{error}"
+        );
+
+        // From 03:00 the clocks go back to 02:00, so hours 3 and 4 are both 02:00-03:00.
+        let starts_of = |hours| starts(&rows("29/10/2023", hours), Format::EDistribucion);
+        assert_eq!(
+            starts_of(1..=5),
+            [
+                (0, false),
+                (60, false),
+                (120, false),
+                (120, true),
+                (180, false)
+            ]
+        );
+        assert_eq!(starts_of(25..=25), [(23 * 60, false)]);
+    }
+
+    #[test]
+    fn ending_times_of_the_clock_changes() {
+        let rows = |date, times: &[&str]| -> String {
+            let mut csv = String::from("CUPS;Fecha;Hora;AE_kWh;REAL/ESTIMADO\n");
+            for time in times {
+                csv += &format!("ES0031;{date};{time};0,100;R\n");
+            }
+            csv
+        };
+
+        let march = rows("2023/03/26", &["01:00", "02:00", "04:00"]);
+        assert_eq!(
+            starts(&march, Format::Datadis),
+            [(0, false), (60, false), (180, false)]
+        );
+
+        // The second reading ending at 03:00 is the repeated 02:00-03:00.
+        let october = rows("2023/10/29", &["02:00", "03:00", "03:00", "04:00"]);
+        assert_eq!(
+            starts(&october, Format::Datadis),
+            [(60, false), (120, false), (120, true), (180, false)]
+        );
+        // Quarter-hourly readings repeat each quarter of the hour.
+        let october = rows("2023/10/29", &["02:45", "03:00", "02:15", "03:00", "03:15"]);
+        assert_eq!(
+            starts(&october, Format::Datadis),
+            [
+                (150, false),
+                (165, false),
+                (120, true),
+                (165, true),
+                (180, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn datadis() {
+        assert_format(
+            "CUPS;Fecha;Hora;AE_kWh;REAL/ESTIMADO\nES0031;2023/01/02;01:00;0,500;E\n",
+            Format::Datadis,
+        );
+    }
+
+    #[test]
+    fn ide() {
+        // The consumption is in Wh.
+        assert_format(
+            "CUPS;FECHA-HORA;INV / VER;CONSUMO Wh;REAL/ESTIMADO\n\
+             ES0021;02/01/2023 01:00;0;500;E\n",
+            Format::Ide,
+        );
+    }
+
+    #[test]
+    fn ufd() {
+        assert_format(
+            "CUPS;Fecha;Hora;Consumo (kWh);Tipo de lectura\nES0022;02/01/2023;1;0,500;Estimada\n",
+            Format::Ufd,
+        );
+    }
+
+    #[test]
+    fn eredes() {
+        assert_format(
+            "CUPS;Fecha;Hora;AE (kWh);Real/Estimado\nES0023;02/01/2023;1;0,500;E\n",
+            Format::Eredes,
+        );
+    }
+
+    #[test]
+    fn quoted_rows() {
+        let csv = "\"CUPS\",\"Fecha\",\"Hora\",\"Consumo_kWh\"\n\
+                   \"ES0001\",\"01/01/2023\",\"1\",\"0.123\"\n";
+        let readings = read(csv).unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].energy.to_string(), "0.123");
+    }
+
+    #[test]
+    fn delimiters_and_decimal_separators() {
+        let csv = "CUPS;Fecha;Hora;Consumo_kWh\nES0001;01/01/2023;1;0\nES0001;01/01/2023;2;1,5\n";
+        assert_eq!(kwh(read(csv)), ["0.000", "1.500"]);
+
+        let csv = "Fecha\tHora\tConsumo_kWh\n01/01/2023\t1\t1.5\n01/01/2023\t2\t2\n";
+        assert_eq!(kwh(read(csv)), ["1.500", "2.000"]);
+
+        // A quoted field can have a decimal comma in a comma separated file.
+        let csv = "Fecha,Hora,Consumo_kWh\n01/01/2023,1,\"1,5\"\n";
+        assert_eq!(kwh(read(csv)), ["1.500"]);
+
+        let csv = "Fecha|Hora|Consumo_kWh\n01/01/2023|1|1,5\n";
+        let options = Options {
+            delimiter: Some('|'),
+            decimal_separator: Some('.'),
+            ..Options::default()
+        };
+        let e = read_with(csv, Format::EDistribucion, options)
+            .unwrap_err()
+            .to_string();
+        assert!(e.contains("invalid consumption '1,5'"), "{e}");
+        let options = Options {
+            delimiter: Some('|'),
+            ..Options::default()
+        };
+        assert_eq!(
+            kwh(read_with(csv, Format::EDistribucion, options)),
+            ["1.500"]
+        );
+    }
+
+    #[test]
+    fn windows_1252_lines() {
+        let csv = b"Fecha;Hora;Consumo_kWh;Direcci\xf3n\n01/01/2023;1;0,100;Ca\x80 1\n";
+        let readings = Reader::new(&csv[..], Format::EDistribucion, &Options::default())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].energy.to_string(), "0.100");
+        assert_eq!(decode_windows_1252(0x80), '€');
+        assert_eq!(decode_windows_1252(0x81), '\u{FFFD}');
+        assert_eq!(decode_windows_1252(0xF3), 'ó');
+    }
+
+    #[test]
+    fn windows_1252_header() {
+        let csv = b"Fecha;Hora;Consumo_kWh;M\xe9todo de obtenci\xf3n\n01/01/2023;1;0,100;E\n";
+        let readings = Reader::new(&csv[..], Format::EDistribucion, &Options::default())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(readings.len(), 1);
+        assert!(readings[0].estimated);
+    }
+
+    #[test]
+    fn utf8_bom() {
+        let csv = "\u{FEFF}Fecha;Hora;Consumo_kWh\n01/01/2023;1;0,100\n";
+        let readings = read(csv).unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].minute, 0);
+    }
+
+    #[test]
+    fn quarter_hours() {
+        let csv = "Fecha;Hora;Consumo_kWh\n\
+                   01/01/2023;00:15;0,1\n\
+                   01/01/2023;00:30;0,2\n\
+                   01/01/2023;00:45;0,3\n\
+                   01/01/2023;01:00;0,4\n\
+                   01/01/2023;01:15;0,5\n";
+        let readings = read(csv).unwrap();
+        let readings: Vec<_> = readings.iter().map(|r| (r.minute, r.minutes)).collect();
+        assert_eq!(readings, [(0, 15), (15, 15), (30, 15), (45, 15), (60, 15)]);
+
+        // 01:00 followed by 02:00 are hourly readings.
+        let csv = "Fecha;Hora;Consumo_kWh\n01/01/2023;01:00;1\n01/01/2023;02:00;1\n";
+        let readings = read(csv).unwrap();
+        let readings: Vec<_> = readings.iter().map(|r| (r.minute, r.minutes)).collect();
+        assert_eq!(readings, [(0, 60), (60, 60)]);
+
+        let csv = "Fecha;Hora;Consumo_kWh\n01/01/2023;00:15;1\n01/01/2023;00:40;1\n";
+        let e = read(csv).unwrap_err().to_string();
+        assert!(
+            e.contains("00:40 isn't aligned with the 15-minute readings"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn reading_methods() {
+        let csv = "Fecha;Hora;Consumo_kWh;M\u{e9}todo de obtenci\u{f3}n\n\
+                   01/01/2023;1;0,1;R\n\
+                   01/01/2023;2;0,1;E\n";
+        let readings = read(csv).unwrap();
+        let estimated: Vec<_> = readings.iter().map(|r| r.estimated).collect();
+        assert_eq!(estimated, [false, true]);
+
+        let csv = "Fecha;Hora;Consumo_kWh\n01/01/2023;1;0,1\n";
+        assert!(!read(csv).unwrap()[0].estimated);
+
+        let csv = "Fecha;Hora;Consumo_kWh;METODO_OBTENCION\n01/01/2023;1;0,1;X\n";
+        let e = read(csv).unwrap_err().to_string();
+        assert!(e.contains("invalid reading method 'X'"), "{e}");
+    }
+
+    #[test]
+    fn column_names() {
+        assert_eq!(
+            normalize_column_name(" Método de  Obtención "),
+            "metodo_de__obtencion"
+        );
+        assert_eq!(normalize_column_name("Consumo_kWh"), "consumo_kwh");
+    }
+
+    #[test]
+    fn decimal_separators() {
+        assert_eq!(decimal_separator_of("1.234,567"), Some(','));
+        assert_eq!(decimal_separator_of("1,234.567"), Some('.'));
+        assert_eq!(decimal_separator_of("1.234.567"), Some(','));
+        assert_eq!(decimal_separator_of("0,123"), Some(','));
+        assert_eq!(decimal_separator_of("1.5"), Some('.'));
+        assert_eq!(decimal_separator_of("1234,567"), Some(','));
+        assert_eq!(decimal_separator_of("1.234"), None);
+        assert_eq!(decimal_separator_of("123"), None);
+    }
+
+    #[test]
+    fn numbers() {
+        let kwh = |s, decimal| parse_energy(s, decimal, Energy::KWH);
+        assert_eq!(kwh("1.234,5", ','), Some(Energy(1_234_500_000)));
+        assert_eq!(kwh("1.234", ','), Some(Energy(1_234_000_000)));
+        assert_eq!(kwh("0.123", '.'), Some(Energy(123_000)));
+        assert_eq!(kwh("12.34", ','), None);
+        assert_eq!(kwh("1e30", '.'), None);
+        assert_eq!(kwh("inf", '.'), None);
+        assert_eq!(kwh("-1", '.'), None);
+        assert_eq!(
+            parse_energy("1.234,5", ',', Energy::WH),
+            Some(Energy(1_234_500))
+        );
+    }
+
+    #[test]
+    fn decimal_separator_settled_after_the_first_rows() {
+        let csv = "Fecha;Hora;Consumo_kWh\n02/01/2023;1;1.234\n02/01/2023;2;0.5\n";
+        assert_eq!(kwh(read(csv)), ["1.234", "0.500"]);
+
+        let csv = "Fecha,Hora,Consumo_kWh\n02/01/2023,1,\"1,234\"\n02/01/2023,2,\"0,5\"\n";
+        assert_eq!(kwh(read(csv)), ["1.234", "0.500"]);
+    }
+
+    #[test]
+    fn decimal_separator_never_settled() {
+        let csv = "Fecha;Hora;Consumo_kWh\n02/01/2023;1;1.234\n02/01/2023;2;2\n";
+        let e = read(csv).unwrap_err().to_string();
+        assert!(e.contains("'1.234' is ambiguous"), "{e}");
+
+        // Comma-separated files have a decimal point unless they tell otherwise.
+        let csv = "Fecha,Hora,Consumo_kWh\n02/01/2023,1,1.234\n02/01/2023,2,2\n";
+        assert_eq!(kwh(read(csv)), ["1.234", "2.000"]);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::date::Date;
+use crate::energy::Energy;
+
+/// Formats of the CSV files exported by the supported platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// "Your consumption" export of e-distribución.
+    #[default]
+    EDistribucion,
+    /// Consumption export of Datadis, the platform of all the Spanish distributors.
+    Datadis,
+    /// Hourly consumption export of i-DE, the distributor of Iberdrola.
+    Ide,
+    /// Hourly consumption export of UFD, the distributor of Naturgy.
+    Ufd,
+    /// Hourly consumption export of E-REDES, formerly Viesgo, the distributor of the north of
+    /// Spain.
+    Eredes,
+    /// One JSON object per line with the start and the kWh of each reading, e.g. exported from
+    /// other tools; it isn't a CSV format.
+    Ndjson,
+    /// History of a cumulative energy sensor exported from Home Assistant.
+    HomeAssistant,
+}
+
+/// Names of the columns of a format, with alternatives for each one, and how to parse them.
+pub struct Layout {
+    pub date: &'static [&'static str],
+    /// Names of the time column; when it's the same column as the date, its values are the date
+    /// and the time separated by a space.
+    pub time: &'static [&'static str],
+    pub kwh: &'static [&'static str],
+    pub method: &'static [&'static str],
+    /// mWh of each unit of the consumption column.
+    pub mwh_per_unit: u64,
+    pub parse_date: fn(&str) -> Option<Date>,
+}
+
+impl Format {
+    pub const ALL: [Format; 7] = [
+        Format::EDistribucion,
+        Format::Datadis,
+        Format::Ide,
+        Format::Ufd,
+        Format::Eredes,
+        Format::Ndjson,
+        Format::HomeAssistant,
+    ];
+
+    /// Parses the format name used in the command-line arguments.
+    pub fn parse(s: &str) -> Option<Self> {
+        Format::ALL.into_iter().find(|f| f.name() == s)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::EDistribucion => "edistribucion",
+            Format::Datadis => "datadis",
+            Format::Ide => "ide",
+            Format::Ufd => "ufd",
+            Format::Eredes => "eredes",
+            Format::Ndjson => "ndjson",
+            Format::HomeAssistant => "homeassistant",
+        }
+    }
+
+    /// Returns the layout of the formats that are read as distributors' CSV files.
+    pub fn layout(self) -> Option<Layout> {
+        let layout = match self {
+            Format::EDistribucion => Layout {
+                date: &["Fecha"],
+                time: &["Hora"],
+                kwh: &["Consumo_kWh"],
+                method: &["Metodo_obtencion", "Metodo de obtencion"],
+                mwh_per_unit: Energy::KWH,
+                parse_date: Date::parse_dmy,
+            },
+            Format::Datadis => Layout {
+                date: &["Fecha", "date"],
+                time: &["Hora", "time"],
+                kwh: &["AE_kWh", "consumptionKWh", "Consumo_kWh"],
+                method: &["REAL/ESTIMADO", "obtainMethod", "Metodo_obtencion"],
+                mwh_per_unit: Energy::KWH,
+                parse_date: Date::parse_ymd,
+            },
+            Format::Ide => Layout {
+                date: &["FECHA-HORA"],
+                time: &["FECHA-HORA"],
+                kwh: &["CONSUMO Wh"],
+                method: &["REAL/ESTIMADO"],
+                mwh_per_unit: Energy::WH,
+                parse_date: Date::parse_dmy,
+            },
+            Format::Ufd => Layout {
+                date: &["Fecha"],
+                time: &["Hora"],
+                kwh: &["Consumo (kWh)", "Consumo kWh", "Consumo"],
+                method: &["Tipo de lectura", "Metodo obtencion"],
+                mwh_per_unit: Energy::KWH,
+                parse_date: Date::parse_dmy,
+            },
+            Format::Eredes => Layout {
+                date: &["Fecha"],
+                time: &["Hora"],
+                kwh: &["AE (kWh)", "Consumo (kWh)", "Consumo_kWh"],
+                method: &["Real/Estimado", "Tipo de lectura"],
+                mwh_per_unit: Energy::KWH,
+                parse_date: Date::parse_dmy,
+            },
+            Format::Ndjson | Format::HomeAssistant => return None,
+        };
+
+        Some(layout)
+    }
+}
+
+/// Parses the reading method column, returning whether the reading is estimated.
+///
+/// Real readings are `R` or `Real` and the estimated ones `E`, `Estimada` or `Estimado`, in any
+/// case.
+pub fn parse_method(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "r" | "real" => Some(false),
+        "e" | "estimada" | "estimado" => Some(true),
+        _ => None,
+    }
+}
+
+/// Columns of a CSV file of a format that isn't supported, declared by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMap {
+    /// Column of the date and time when each reading starts.
+    pub timestamp: String,
+    /// Column of the consumption in kWh.
+    pub value: String,
+    /// Column of the reading method, if any.
+    pub method: Option<String>,
+    /// Pattern of the timestamps; see [`parse_timestamp`].
+    pub format: String,
+}
+
+/// Parses `s` according to `pattern`, returning the date and the minute of the day.
+///
+/// The pattern is made of the `%d` (day), `%m` (month), `%Y` (4-digit year), `%H` (hour, 0-23),
+/// `%M` (minute) and `%%` (a literal `%`) specifiers and literal characters that `s` must
+/// contain as they are. Days, months, hours and minutes can have 1 or 2 digits.
+pub fn parse_timestamp(pattern: &str, s: &str) -> Option<(Date, u16)> {
+    let (mut year, mut month, mut day, mut hour, mut minute) = (None, None, None, 0, 0);
+    let mut rest = s;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            rest = rest.strip_prefix(c)?;
+            continue;
+        }
+
+        let spec = chars.next()?;
+        if spec == '%' {
+            rest = rest.strip_prefix('%')?;
+            continue;
+        }
+
+        let max_digits = if spec == 'Y' { 4 } else { 2 };
+        let len = rest
+            .bytes()
+            .take(max_digits)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if len == 0 || (spec == 'Y' && len != 4) {
+            return None;
+        }
+
+        let value: u16 = rest[..len].parse().ok()?;
+        rest = &rest[len..];
+        match spec {
+            'Y' => year = Some(i32::from(value)),
+            'm' => month = Some(u8::try_from(value).ok()?),
+            'd' => day = Some(u8::try_from(value).ok()?),
+            'H' if value < 24 => hour = value,
+            'M' if value < 60 => minute = value,
+            _ => return None,
+        }
+    }
+
+    if !rest.is_empty() {
+        return None;
+    }
+
+    Some((Date::new(year?, month?, day?)?, hour * 60 + minute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        let date = |y, m, d| Date::new(y, m, d).unwrap();
+        assert_eq!(
+            parse_timestamp("%d/%m/%Y %H:%M", "02/01/2023 13:45"),
+            Some((date(2023, 1, 2), 13 * 60 + 45))
+        );
+        assert_eq!(
+            parse_timestamp("%Y%m%d%H", "2023010213"),
+            Some((date(2023, 1, 2), 13 * 60))
+        );
+        assert_eq!(
+            parse_timestamp("%d.%m.%Y %Hh (100%%)", "2.1.2023 7h (100%)"),
+            Some((date(2023, 1, 2), 7 * 60))
+        );
+        assert_eq!(parse_timestamp("%d/%m/%Y %H", "02/01/2023 24"), None);
+        assert_eq!(parse_timestamp("%d/%m/%Y %H", "30/02/2023 1"), None);
+        assert_eq!(parse_timestamp("%d/%m/%Y %H", "02/01/23 1"), None);
+        assert_eq!(parse_timestamp("%d/%m/%Y %H", "02/01/2023 1:00"), None);
+        assert_eq!(parse_timestamp("%d/%m/%Y %H", "02-01-2023 1"), None);
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::BufRead;
+
+use super::Reading;
+use crate::energy::Energy;
+use crate::error::Error;
+use crate::timezone::{is_repeated, parse_rfc3339, to_local};
+
+/// Sensor states that Home Assistant records when the sensor has no value.
+const NO_VALUE_STATES: [&str; 3] = ["", "unavailable", "unknown"];
+
+/// Fraction of the previous state below which a lower state is a reset of the sensor rather than
+/// a glitch, as Home Assistant does with its `total_increasing` sensors.
+const RESET_FRACTION: f64 = 0.9;
+
+/// Reads the history of a cumulative energy sensor exported from Home Assistant as CSV, with the
+/// `entity_id`, `state` and `last_changed` columns, into hourly readings in local time.
+///
+/// The states are the kWh that the sensor has counted, which can go back to 0 when it's reset,
+/// and `last_changed` is the UTC time when it changed. A state lower than the previous one is a
+/// reset when it drops below 90% of it, and it's ignored otherwise. The value at each o'clock is
+/// interpolated between the states around it, so the readings are the hours between the first and
+/// the last state, including both of the hour repeated when the clocks go back.
+pub fn home_assistant_readings(mut input: impl BufRead) -> Result<Vec<Reading>, Error> {
+    let mut line = String::new();
+    let mut line_num = 0;
+    let mut next_line = |line: &mut String| -> Result<bool, Error> {
+        line.clear();
+        line_num += 1;
+        input
+            .read_line(line)
+            .map(|n| n > 0)
+            .map_err(|e| Error::csv(line_num, e.to_string()))
+    };
+
+    if !next_line(&mut line)? {
+        return Err(Error::csv(1, "there is no data, not even the header row"));
+    }
+    let header: Vec<String> = line
+        .trim_start_matches('\u{FEFF}')
+        .split(',')
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| Error::csv(1, format!("column {name} not found in the header")))
+    };
+    let (entity_col, state_col, changed_col) = (
+        column("entity_id")?,
+        column("state")?,
+        column("last_changed")?,
+    );
+
+    let mut entities: Vec<String> = Vec::new();
+    // UTC instants and cumulative kWh of the states, corrected for the resets.
+    let mut samples: Vec<(i64, f64)> = Vec::new();
+    let (mut offset, mut last_value) = (0.0, None);
+    let mut n = 1;
+    while next_line(&mut line)? {
+        n += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |col: usize| {
+            fields
+                .get(col)
+                .copied()
+                .ok_or_else(|| Error::csv(n, format!("missing column {}", header[col])))
+        };
+
+        let entity = field(entity_col)?;
+        if !entities.iter().any(|e| e == entity) {
+            entities.push(entity.to_string());
+        }
+
+        let state = field(state_col)?;
+        if NO_VALUE_STATES.contains(&state) {
+            continue;
+        }
+        let value: f64 = state
+            .parse()
+            .ok()
+            .filter(|v: &f64| v.is_finite())
+            .ok_or_else(|| Error::csv(n, format!("invalid state '{state}'")))?;
+        let changed = field(changed_col)?;
+        let instant = parse_rfc3339(changed)
+            .ok_or_else(|| Error::csv(n, format!("invalid timestamp '{changed}'")))?;
+
+        if samples.last().is_some_and(|&(t, _)| instant < t) {
+            return Err(Error::csv(n, "the states aren't in chronological order"));
+        }
+        if let Some(last) = last_value {
+            if value < last * RESET_FRACTION {
+                offset += last;
+            } else if value < last {
+                continue;
+            }
+        }
+        last_value = Some(value);
+        samples.push((instant, offset + value));
+    }
+
+    if entities.len() > 1 {
+        return Err(Error::csv(
+            n,
+            format!(
+                "the file has the history of several entities, {}; export only one",
+                entities.join(", ")
+            ),
+        ));
+    }
+
+    Ok(hourly(&samples))
+}
+
+/// Splits the cumulative `samples`, sorted chronologically, into hourly readings.
+fn hourly(samples: &[(i64, f64)]) -> Vec<Reading> {
+    let (Some(&(first, _)), Some(&(last, _))) = (samples.first(), samples.last()) else {
+        return Vec::new();
+    };
+
+    // Cumulative value at `instant`, between the first and the last samples.
+    let mut i = 0;
+    let mut value_at = |instant: i64| {
+        while samples[i + 1].0 < instant {
+            i += 1;
+        }
+        let ((t0, v0), (t1, v1)) = (samples[i], samples[i + 1]);
+        if t1 == t0 {
+            v1
+        } else {
+            v0 + (v1 - v0) * (instant - t0) as f64 / (t1 - t0) as f64
+        }
+    };
+
+    let mut readings: Vec<Reading> = Vec::new();
+    let mut hour = first + (60 - first.rem_euclid(60)) % 60;
+    if hour + 60 > last {
+        return readings;
+    }
+
+    // The cumulative mWh are rounded before subtracting them, so that the readings add up to
+    // the rounded consumption between the first and the last o'clock.
+    let mut mwh_at = |instant| (value_at(instant) * Energy::KWH as f64).round();
+    let mut start_value = mwh_at(hour);
+    while hour + 60 <= last {
+        let end_value = mwh_at(hour + 60);
+        // The subtraction of whole numbers is exact; a negative one saturates to 0.
+        let energy = Energy((end_value - start_value) as u64);
+        let (date, minute) = to_local(hour);
+        readings.push(Reading {
+            date,
+            minute,
+            minutes: 60,
+            energy,
+            estimated: false,
+            repeated: is_repeated(hour),
+        });
+
+        start_value = end_value;
+        hour += 60;
+    }
+
+    readings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total_kwh(states: &[&str]) -> String {
+        let mut csv = String::from("entity_id,state,last_changed\n");
+        for (h, state) in states.iter().enumerate() {
+            csv.push_str(&format!("sensor.energy,{state},2023-01-02T{h:02}:00:00Z\n"));
+        }
+
+        let readings = home_assistant_readings(csv.as_bytes()).unwrap();
+        Energy(readings.iter().map(|r| r.energy.0).sum()).to_string()
+    }
+
+    #[test]
+    fn small_decreases_are_ignored() {
+        assert_eq!(total_kwh(&["100.0", "100.5", "100.4", "101.0"]), "1.000");
+    }
+
+    #[test]
+    fn resets() {
+        assert_eq!(total_kwh(&["100.0", "101.0", "0.5", "1.0"]), "2.000");
+        assert_eq!(total_kwh(&["10.0", "11.0", "8.0", "9.0"]), "10.000");
+    }
+}
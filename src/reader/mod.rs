@@ -0,0 +1,421 @@
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+mod csv;
+mod format;
+mod home_assistant;
+mod ndjson;
+
+use csv::UTF8_BOM;
+pub use csv::{detect_format, Reader};
+pub use format::{ColumnMap, Format};
+pub use home_assistant::home_assistant_readings;
+pub use ndjson::NdjsonReader;
+
+use crate::compressed::Compression;
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::error::Error;
+use crate::quality::Quality;
+
+/// The consumption of a time interval of a day.
+#[derive(Debug, Clone, Copy)]
+pub struct Reading {
+    pub date: Date,
+    /// Minute of the day when the reading starts.
+    pub minute: u16,
+    /// Duration of the reading in minutes: 60 for hourly readings and 15 for quarter-hourly ones.
+    pub minutes: u16,
+    pub energy: Energy,
+    /// Whether the distributor estimated the consumption rather than reading it from the meter.
+    pub estimated: bool,
+    /// Whether it's the second reading of the hour that the clocks repeat when they go back in
+    /// October.
+    pub repeated: bool,
+}
+
+/// Iterator over the readings of a file.
+pub type Readings = Box<dyn Iterator<Item = Result<Reading, Error>>>;
+
+/// Returns the readings of the file at `path` (see [`open`]), aggregating the quarter-hourly ones
+/// into hourly readings unless `options.keep_quarter_hours` is set; errors refer to `path`.
+///
+/// Without a format in the options, it's detected from the beginning of the file (see
+/// [`detect_format`]) and returned with the readings.
+pub fn readings(path: &Path, options: &Options) -> Result<(Readings, Option<Format>), Error> {
+    let input = open(path)?;
+    let (format, detected, input) = match (options.format, &options.column_map) {
+        (_, Some(_)) => (Format::default(), None, input),
+        (Some(format), None) => (format, None, input),
+        (None, None) => {
+            let (sample, input) = read_sample(input).map_err(|source| Error::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            if sample
+                .strip_prefix(UTF8_BOM)
+                .unwrap_or(&sample)
+                .trim_ascii()
+                .is_empty()
+            {
+                return Err(
+                    Error::csv(1, "there is no data, not even the header row").in_file(path)
+                );
+            }
+
+            let format = sniff_format(&sample, options.delimiter).ok_or_else(|| {
+                Error::csv(
+                    1,
+                    "the format isn't any of the supported ones, use --input-format",
+                )
+                .in_file(path)
+            })?;
+            (format, Some(format), input)
+        }
+    };
+
+    let reader: Readings = match format {
+        Format::Ndjson => Box::new(NdjsonReader::new(input)),
+        Format::HomeAssistant => {
+            let readings = home_assistant_readings(input).map_err(|e| e.in_file(path))?;
+            Box::new(readings.into_iter().map(Ok))
+        }
+        _ => Box::new(Reader::new(input, format, options).map_err(|e| e.in_file(path))?),
+    };
+    let path = path.to_path_buf();
+    let readings = reader.map(move |r| r.map_err(|e| e.in_file(&path)));
+    if options.keep_quarter_hours {
+        Ok((Box::new(readings), detected))
+    } else {
+        Ok((Box::new(Hourly::new(readings)), detected))
+    }
+}
+
+/// Detects the format of the file whose first bytes are `sample`: NDJSON if it starts with an
+/// object, Home Assistant if its header starts with `entity_id`, and otherwise the CSV format
+/// that [`detect_format`] detects.
+fn sniff_format(sample: &[u8], delimiter: Option<char>) -> Option<Format> {
+    let text = sample.strip_prefix(UTF8_BOM).unwrap_or(sample);
+    if text.trim_ascii_start().starts_with(b"{") {
+        Some(Format::Ndjson)
+    } else if text.starts_with(b"entity_id,") {
+        Some(Format::HomeAssistant)
+    } else {
+        detect_format(sample, delimiter)
+    }
+}
+
+/// Maximum number of bytes read in advance to detect the format of a file.
+const SAMPLE_LIMIT: usize = 64 * 1024;
+
+/// Reads the beginning of `input` until it has two complete non-blank lines, the header and the
+/// first data row, or [`SAMPLE_LIMIT`] bytes, or up to its end; returns the sample and the input,
+/// which still starts with the sample.
+fn read_sample(mut input: Box<dyn BufRead>) -> io::Result<(Vec<u8>, Box<dyn BufRead>)> {
+    let mut sample = Vec::new();
+    loop {
+        let complete = match sample.iter().rposition(|&b| b == b'\n') {
+            Some(end) => &sample[..end],
+            None => &[][..],
+        };
+        let lines = complete
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.trim_ascii().is_empty())
+            .count();
+        if lines >= 2 || sample.len() >= SAMPLE_LIMIT {
+            break;
+        }
+
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let n = buf.len().min(SAMPLE_LIMIT - sample.len());
+        sample.extend_from_slice(&buf[..n]);
+        input.consume(n);
+    }
+
+    let input = Box::new(Cursor::new(sample.clone()).chain(input));
+    Ok((sample, input))
+}
+
+/// Opens the CSV file at `path` for reading, or the standard input when `path` is `-`.
+///
+/// gzip and zip compressed files are detected by their magic bytes and decompressed as they are
+/// read; zip archives must contain a single CSV file.
+pub fn open(path: &Path) -> Result<Box<dyn BufRead>, Error> {
+    let io_err = |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let mut input: Box<dyn BufRead> = if path == Path::new("-") {
+        let stdin = io::stdin();
+        if stdin.is_terminal() {
+            return Err(io_err(io::Error::other(
+                "it's a terminal, pipe the CSV data into it instead",
+            )));
+        }
+
+        Box::new(stdin.lock())
+    } else {
+        Box::new(BufReader::new(File::open(path).map_err(io_err)?))
+    };
+
+    let Some(compression) = Compression::detect(input.fill_buf().map_err(io_err)?) else {
+        return Ok(input);
+    };
+
+    compression.decoder(input).map_err(io_err)
+}
+
+/// Options to read the CSV files; the unset ones are detected from the file content.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub format: Option<Format>,
+    /// Columns of a CSV file of any other format; it takes precedence over `format`.
+    pub column_map: Option<ColumnMap>,
+    /// Character separating the fields.
+    pub delimiter: Option<char>,
+    /// Character separating the integer from the fractional part of the consumption values.
+    pub decimal_separator: Option<char>,
+    /// Don't aggregate quarter-hourly readings into hourly ones.
+    pub keep_quarter_hours: bool,
+}
+
+/// Aggregates consecutive readings shorter than an hour of the same hour into an hourly reading,
+/// which is estimated if any of them is.
+pub struct Hourly<I> {
+    inner: I,
+    pending: Option<Reading>,
+}
+
+impl<I> Hourly<I> {
+    pub fn new(inner: I) -> Self {
+        Hourly {
+            inner,
+            pending: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<Reading, Error>>> Iterator for Hourly<I> {
+    type Item = Result<Reading, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut hour = match self.pending.take() {
+            Some(r) => r,
+            None => match self.inner.next()? {
+                Ok(r) => r,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+        if hour.minutes >= 60 {
+            return Some(Ok(hour));
+        }
+
+        hour.minute -= hour.minute % 60;
+        hour.minutes = 60;
+        loop {
+            match self.inner.next() {
+                Some(Ok(r))
+                    if r.minutes < 60
+                        && r.date == hour.date
+                        && r.minute / 60 == hour.minute / 60
+                        && r.repeated == hour.repeated =>
+                {
+                    hour.energy = hour.energy.saturating_add(r.energy);
+                    hour.estimated |= r.estimated;
+                }
+                Some(Ok(r)) => {
+                    self.pending = Some(r);
+                    break;
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => break,
+            }
+        }
+
+        Some(Ok(hour))
+    }
+}
+
+/// Readings of several files merged chronologically.
+pub struct Merged {
+    pub readings: Vec<Reading>,
+    /// Number of readings discarded because a previous file already had their timestamp.
+    pub duplicates: usize,
+    /// Check of each file in the order of its rows, whose duplicates are already discarded.
+    pub quality: Vec<Quality>,
+    /// Formats detected from the content of the files, for those without a format in the
+    /// options.
+    pub detected: Vec<(PathBuf, Format)>,
+}
+
+/// Reads all the files at `paths` and merges their readings chronologically; when several files
+/// have a reading with the same timestamp, the one of the first file is kept.
+pub fn merge(paths: &[PathBuf], options: &Options) -> Result<Merged, Error> {
+    let mut merged = BTreeMap::new();
+    let mut duplicates = 0;
+    let mut quality = Vec::with_capacity(paths.len());
+    let mut detected = Vec::new();
+    for path in paths {
+        let mut file_quality = Quality::default();
+        let (file_readings, format) = readings(path, options)?;
+        if let Some(format) = format {
+            detected.push((path.clone(), format));
+        }
+        for r in file_readings {
+            let r = r?;
+            if !file_quality.check(&r) {
+                continue;
+            }
+
+            match merged.entry((r.date, r.minute, r.repeated)) {
+                Entry::Vacant(e) => {
+                    e.insert(r);
+                }
+                Entry::Occupied(_) => duplicates += 1,
+            }
+        }
+        quality.push(file_quality);
+    }
+
+    Ok(Merged {
+        readings: merged.into_values().collect(),
+        duplicates,
+        quality,
+        detected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_missing_file() {
+        let path = Path::new("/nonexistent/consumption.csv");
+        let e = open(path).err().unwrap().to_string();
+        assert!(e.contains("/nonexistent/consumption.csv"), "{e}");
+    }
+
+    #[test]
+    fn merge_files() {
+        let dir = std::env::temp_dir().join(format!("electricity-meter-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            "Fecha,Hora,Consumo_kWh\n01/01/2023,2,0.2\n01/01/2023,1,0.1\n",
+            "Fecha,Hora,Consumo_kWh\n01/01/2023,1,9\n01/01/2023,3,0.3\n",
+        ];
+        let paths: Vec<_> = files
+            .iter()
+            .enumerate()
+            .map(|(i, csv)| {
+                let path = dir.join(format!("merge-{i}.csv"));
+                std::fs::write(&path, csv).unwrap();
+                path
+            })
+            .collect();
+
+        let merged = merge(&paths, &Options::default()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let readings: Vec<_> = merged
+            .readings
+            .iter()
+            .map(|r| (r.minute, r.energy))
+            .collect();
+        assert_eq!(
+            readings,
+            [
+                (0, Energy(100_000)),
+                (60, Energy(200_000)),
+                (120, Energy(300_000)),
+            ]
+        );
+        assert_eq!(merged.duplicates, 1);
+        let detected: Vec<_> = merged.detected.iter().map(|(_, f)| *f).collect();
+        assert_eq!(detected, [Format::EDistribucion, Format::EDistribucion]);
+    }
+
+    #[test]
+    fn hourly() {
+        let csv = "Fecha;Hora;Consumo_kWh;Metodo_obtencion\n\
+                   01/01/2023;00:15;0,1;R\n\
+                   01/01/2023;00:30;0,2;R\n\
+                   01/01/2023;00:45;0,3;E\n\
+                   01/01/2023;01:00;0,4;R\n\
+                   01/01/2023;01:15;0,5;R\n";
+        let reader =
+            Reader::new(csv.as_bytes(), Format::EDistribucion, &Options::default()).unwrap();
+        let hourly: Vec<_> = Hourly::new(reader)
+            .map(|r| r.unwrap())
+            .map(|r| (r.minute, r.minutes, r.energy, r.estimated))
+            .collect();
+        assert_eq!(
+            hourly,
+            [
+                (0, 60, Energy(1_000_000), true),
+                (60, 60, Energy(500_000), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn formats() {
+        let cases = [
+            (
+                "CUPS;Fecha;Hora;Consumo_kWh;Metodo_obtencion\nES0031;02/01/2023;1;0,1;R\n",
+                Format::EDistribucion,
+            ),
+            // Both e-distribución and Datadis have these columns; the date tells them apart.
+            (
+                "CUPS;Fecha;Hora;Consumo_kWh;Metodo_obtencion\nES0031;2023/01/02;1;0,1;R\n",
+                Format::Datadis,
+            ),
+            // Without data rows, the first format with the columns.
+            ("CUPS;Fecha;Hora;Consumo_kWh\n", Format::EDistribucion),
+            (
+                "cups;date;time;consumptionKWh;obtainMethod\nES0031;2023/01/02;01:00;0,1;Real\n",
+                Format::Datadis,
+            ),
+            (
+                "CUPS;FECHA-HORA;INV / VER;CONSUMO Wh;REAL/ESTIMADO\n\
+                 ES0021;02/01/2023 01:00;0;100;R\n",
+                Format::Ide,
+            ),
+            (
+                "CUPS;Fecha;Hora;Consumo (kWh);Tipo de lectura\nES0022;02/01/2023;1;0,1;Real\n",
+                Format::Ufd,
+            ),
+            (
+                "CUPS;Fecha;Hora;AE (kWh);Real/Estimado\nES0023;02/01/2023;1;0,1;R\n",
+                Format::Eredes,
+            ),
+            (
+                "\u{FEFF}  {\"ts\": \"2023-01-02T00:00\", \"kwh\": 0.1}\n",
+                Format::Ndjson,
+            ),
+            (
+                "\u{FEFF}entity_id,state,last_changed\nsensor.energy,1.5,2023-01-02T00:00:00Z\n",
+                Format::HomeAssistant,
+            ),
+        ];
+        for (sample, format) in cases {
+            assert_eq!(
+                sniff_format(sample.as_bytes(), None),
+                Some(format),
+                "{sample}"
+            );
+        }
+
+        assert_eq!(sniff_format(b"Date,kWh\n2023-01-02,1\n", None), None);
+        // The date doesn't match any of the formats with these columns.
+        assert_eq!(
+            sniff_format(b"Fecha;Hora;Consumo_kWh\n2023-01-02;1;0,1\n", None),
+            None
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use std::io::BufRead;
+
+use super::Reading;
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::error::Error;
+use crate::timezone::{is_repeated, parse_date_time, parse_rfc3339, to_local};
+
+/// Streams the readings of an NDJSON file: one JSON object per line with the `ts` and `kwh`
+/// members, e.g. `{"ts": "2023-01-01T00:00", "kwh": 0.25}`; the rest of members are ignored.
+///
+/// `ts` is the date and time when the reading starts, `YYYY-MM-DDTHH:MM` optionally followed by
+/// seconds and a UTC offset or `Z`; a space can replace the `T`. Times without offset are local
+/// and the rest are converted to local time, which tells apart the hour repeated when the clocks
+/// go back. Readings are hourly unless the first ones are 15 minutes apart.
+pub struct NdjsonReader<R> {
+    input: R,
+    line: String,
+    line_num: usize,
+    /// Minutes of each reading, once detected.
+    resolution: Option<u16>,
+    /// Record read in advance to detect the resolution.
+    pending: Option<Result<Record, Error>>,
+}
+
+/// A parsed NDJSON line.
+struct Record {
+    line_num: usize,
+    date: Date,
+    /// Minute of the day when the reading starts.
+    minute: u16,
+    energy: Energy,
+    repeated: bool,
+}
+
+impl<R: BufRead> NdjsonReader<R> {
+    pub fn new(input: R) -> Self {
+        NdjsonReader {
+            input,
+            line: String::new(),
+            line_num: 0,
+            resolution: None,
+            pending: None,
+        }
+    }
+
+    /// Reads and parses the next non-blank line.
+    fn next_record(&mut self) -> Option<Result<Record, Error>> {
+        if let Some(record) = self.pending.take() {
+            return Some(record);
+        }
+
+        loop {
+            self.line.clear();
+            match self.input.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_num += 1,
+                Err(e) => return Some(Err(Error::csv(self.line_num + 1, e.to_string()))),
+            }
+
+            if !self.line.trim().is_empty() {
+                return Some(self.parse_line());
+            }
+        }
+    }
+
+    fn parse_line(&self) -> Result<Record, Error> {
+        let n = self.line_num;
+        let line = self.line.trim();
+        let line = line.strip_prefix('\u{FEFF}').unwrap_or(line);
+        let members =
+            parse_object(line).map_err(|e| Error::csv(n, format!("invalid JSON: {e}")))?;
+
+        let member = |name: &str| {
+            members
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v)
+                .ok_or_else(|| Error::csv(n, format!("missing member {name}")))
+        };
+        let (date, minute, repeated) = match member("ts")? {
+            Value::String(ts) => {
+                parse_ts(ts).ok_or_else(|| Error::csv(n, format!("invalid timestamp '{ts}'")))?
+            }
+            _ => return Err(Error::csv(n, "ts isn't a string")),
+        };
+        let energy = match member("kwh")? {
+            Value::Number(kwh) => Energy::from_kwh(*kwh),
+            _ => None,
+        }
+        .ok_or_else(|| Error::csv(n, "kwh isn't a finite non-negative number"))?;
+
+        Ok(Record {
+            line_num: n,
+            date,
+            minute,
+            energy,
+            repeated,
+        })
+    }
+
+    /// Detects the resolution from the first record, reading the next one in advance when the
+    /// first record doesn't tell it.
+    fn detect_resolution(&mut self, first: &Record) -> u16 {
+        if !first.minute.is_multiple_of(60) {
+            return 15;
+        }
+
+        let Some(next) = self.next_record() else {
+            return 60;
+        };
+        let resolution = match &next {
+            Ok(r) if r.date == first.date && r.minute == first.minute + 15 => 15,
+            _ => 60,
+        };
+
+        self.pending = Some(next);
+        resolution
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonReader<R> {
+    type Item = Result<Reading, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = match self.next_record()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let resolution = match self.resolution {
+            Some(r) => r,
+            None => {
+                let r = self.detect_resolution(&record);
+                self.resolution = Some(r);
+                r
+            }
+        };
+        if !record.minute.is_multiple_of(resolution) {
+            return Some(Err(Error::csv(
+                record.line_num,
+                format!("the timestamp isn't aligned with the {resolution}-minute readings"),
+            )));
+        }
+
+        Some(Ok(Reading {
+            date: record.date,
+            minute: record.minute,
+            minutes: resolution,
+            energy: record.energy,
+            estimated: false,
+            repeated: record.repeated,
+        }))
+    }
+}
+
+/// Parses a `YYYY-MM-DDTHH:MM[:SS][offset]` timestamp into the local date, the minute of the day
+/// and whether it's in the repeated hour.
+fn parse_ts(ts: &str) -> Option<(Date, u16, bool)> {
+    if let Some(utc) = parse_rfc3339(ts) {
+        let (date, minute) = to_local(utc);
+        return Some((date, minute, is_repeated(utc)));
+    }
+
+    let (date, minute, rest) = parse_date_time(ts)?;
+    rest.is_empty().then_some((date, minute, false))
+}
+
+/// Value of a JSON object member; the nested values are only validated.
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Number(f64),
+    Other,
+}
+
+/// Parses a JSON object into its members.
+fn parse_object(s: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut p = Parser { s, pos: 0 };
+    let mut members = Vec::new();
+    p.expect('{')?;
+    if !p.eat('}') {
+        loop {
+            let key = p.string()?;
+            p.expect(':')?;
+            members.push((key, p.value()?));
+            if p.eat('}') {
+                break;
+            }
+            p.expect(',')?;
+        }
+    }
+
+    p.skip_whitespace();
+    if p.pos != s.len() {
+        return Err("unexpected characters after the object".into());
+    }
+
+    Ok(members)
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.s[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected '{c}' at column {}", self.pos + 1))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('{' | '[') => {
+                self.nested()?;
+                Ok(Value::Other)
+            }
+            Some('-' | '0'..='9') => Ok(Value::Number(self.number()?)),
+            _ => {
+                for literal in ["true", "false", "null"] {
+                    if self.s[self.pos..].starts_with(literal) {
+                        self.pos += literal.len();
+                        return Ok(Value::Other);
+                    }
+                }
+
+                Err(format!("invalid value at column {}", self.pos + 1))
+            }
+        }
+    }
+
+    /// Skips a nested object or array.
+    fn nested(&mut self) -> Result<(), String> {
+        let close = if self.eat('{') { '}' } else { ']' };
+        if close == ']' {
+            self.expect('[')?;
+        }
+        if self.eat(close) {
+            return Ok(());
+        }
+
+        loop {
+            if close == '}' {
+                self.string()?;
+                self.expect(':')?;
+            }
+            self.value()?;
+            if self.eat(close) {
+                return Ok(());
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let rest = &self.s[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(rest.len());
+        let n = rest[..len]
+            .parse()
+            .map_err(|_| format!("invalid number at column {}", self.pos + 1))?;
+        self.pos += len;
+        Ok(n)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut out = String::new();
+        let mut chars = self.s[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let mut code = hex_escape(&mut chars)?;
+                        // Characters out of the BMP are escaped as a UTF-16 surrogate pair.
+                        if (0xD800..0xDC00).contains(&code)
+                            && self.s[self.pos..].get(i + 6..i + 8) == Some("\\u")
+                        {
+                            let mut low = chars.clone();
+                            low.nth(1);
+                            let low_code = hex_escape(&mut low)?;
+                            if (0xDC00..0xE000).contains(&low_code) {
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low_code - 0xDC00);
+                                chars = low;
+                            }
+                        }
+                        out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                    }
+                    _ => return Err("invalid escape in a string".into()),
+                },
+                c => out.push(c),
+            }
+        }
+
+        Err("unterminated string".into())
+    }
+}
+
+/// Parses the 4 hexadecimal digits of a `\\u` escape.
+fn hex_escape(chars: &mut std::str::CharIndices) -> Result<u32, String> {
+    let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+    if hex.len() != 4 {
+        return Err(format!("invalid escape \\u{hex}"));
+    }
+    u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape \\u{hex}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects() {
+        let members = parse_object(
+            r#" { "ts" : "a\"\\\/\b\f\n\r\t\u00e9\ud83d\ude00", "kwh":-1.5e1,
+                "x": {"y": [1, {}, []]}, "t": true, "f": false, "n": null, "e": [] } "#,
+        )
+        .unwrap();
+        assert_eq!(
+            members,
+            [
+                (
+                    "ts".into(),
+                    Value::String("a\"\\/\u{8}\u{c}\n\r\té😀".into())
+                ),
+                ("kwh".into(), Value::Number(-15.0)),
+                ("x".into(), Value::Other),
+                ("t".into(), Value::Other),
+                ("f".into(), Value::Other),
+                ("n".into(), Value::Other),
+                ("e".into(), Value::Other),
+            ]
+        );
+        assert_eq!(parse_object("{}"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn invalid_objects() {
+        let cases = [
+            (r#"{"a": 1} x"#, "unexpected characters after the object"),
+            (r#"{"a": 1"#, "expected ',' at column 8"),
+            (r#"{"a" 1}"#, "expected ':' at column 6"),
+            (r#"{"a": tru}"#, "invalid value at column 7"),
+            (r#"{"a": -}"#, "invalid number at column 7"),
+            (r#"{"a": "b}"#, "unterminated string"),
+            (r#"{"a": "\x"}"#, "invalid escape in a string"),
+            (r#"{"a": "\u00"}"#, "invalid escape \\u00\"}"),
+            (r#"{"a": [1, 2}"#, "expected ',' at column 12"),
+            (r#"[1]"#, "expected '{' at column 1"),
+        ];
+        for (json, error) in cases {
+            assert_eq!(parse_object(json), Err(error.to_string()), "{json}");
+        }
+    }
+
+    /// Reads the lines of the `(ts, kwh)` records, with a blank line after the first one.
+    fn read(records: &[(&str, &str)]) -> Result<Vec<Reading>, Error> {
+        let mut ndjson = String::new();
+        for (i, (ts, kwh)) in records.iter().enumerate() {
+            ndjson.push_str(&format!("{{\"ts\": \"{ts}\", \"kwh\": {kwh}}}\n"));
+            if i == 0 {
+                ndjson.push('\n');
+            }
+        }
+
+        NdjsonReader::new(ndjson.as_bytes()).collect()
+    }
+
+    #[test]
+    fn resolutions() {
+        let hourly = read(&[("2023-01-02T00:00", "0.5"), ("2023-01-02 01:00", "1")]).unwrap();
+        assert_eq!(hourly.len(), 2);
+        assert!(hourly.iter().all(|r| r.minutes == 60));
+        assert_eq!(
+            (hourly[1].minute, hourly[1].energy),
+            (60, Energy(1_000_000))
+        );
+
+        let quarters = read(&[("2023-01-02T00:00", "0.1"), ("2023-01-02T00:15", "0.1")]).unwrap();
+        assert!(quarters.iter().all(|r| r.minutes == 15));
+
+        let quarters = read(&[("2023-01-02T00:45", "0.1")]).unwrap();
+        assert_eq!((quarters[0].minute, quarters[0].minutes), (45, 15));
+
+        let error = read(&[("2023-01-02T00:00", "0.1"), ("2023-01-02T01:30", "0.1")]).unwrap_err();
+        assert!(error.to_string().contains("60-minute"), "{error}");
+    }
+
+    #[test]
+    fn utc_timestamps() {
+        // 00:00 UTC is 02:00 of the summer time, and 01:00 UTC is 02:00 again once the clocks
+        // go back on 2023-10-29.
+        let readings = read(&[
+            ("2023-10-29T00:00:00Z", "1"),
+            ("2023-10-29T02:00:00+01:00", "1"),
+        ])
+        .unwrap();
+        let starts: Vec<(u16, bool)> = readings.iter().map(|r| (r.minute, r.repeated)).collect();
+        assert_eq!(starts, [(120, false), (120, true)]);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let cases = [
+            (r#"{"kwh": 1}"#, "missing member ts"),
+            (r#"{"ts": 1, "kwh": 1}"#, "ts isn't a string"),
+            (
+                r#"{"ts": "2023-01-02", "kwh": 1}"#,
+                "invalid timestamp '2023-01-02'",
+            ),
+            (
+                r#"{"ts": "2023-01-02T00:00", "kwh": -1}"#,
+                "kwh isn't a finite",
+            ),
+            (
+                r#"{"ts": "2023-01-02T00:00", "kwh": "1"}"#,
+                "kwh isn't a finite",
+            ),
+            (
+                r#"{"ts": "2023-01-02T00:00", "kwh": 1e999}"#,
+                "kwh isn't a finite",
+            ),
+        ];
+        for (json, error) in cases {
+            let e = NdjsonReader::new(json.as_bytes())
+                .next()
+                .unwrap()
+                .unwrap_err();
+            assert!(e.to_string().contains(error), "{json}: {e}");
+        }
+    }
+}
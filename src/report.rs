@@ -0,0 +1,512 @@
+//! Daily report whose columns are selected by the user.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::calendar::DayType;
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::period::Period;
+use crate::reader::Reading;
+
+/// Fields of each day that the report can show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Date,
+    DayType,
+    TotalKwh,
+    P1Kwh,
+    P2Kwh,
+    P3Kwh,
+    /// kWh from the first day of the readings to the end of the day.
+    CumulativeKwh,
+    /// Number of readings of the day.
+    Readings,
+    /// Number of estimated readings of the day.
+    Estimated,
+}
+
+impl Field {
+    pub const ALL: [Field; 9] = [
+        Field::Date,
+        Field::DayType,
+        Field::TotalKwh,
+        Field::P1Kwh,
+        Field::P2Kwh,
+        Field::P3Kwh,
+        Field::CumulativeKwh,
+        Field::Readings,
+        Field::Estimated,
+    ];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Field::ALL.into_iter().find(|f| f.name() == s)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Date => "date",
+            Field::DayType => "day_type",
+            Field::TotalKwh => "total_kwh",
+            Field::P1Kwh => "p1_kwh",
+            Field::P2Kwh => "p2_kwh",
+            Field::P3Kwh => "p3_kwh",
+            Field::CumulativeKwh => "cumulative_kwh",
+            Field::Readings => "readings",
+            Field::Estimated => "estimated",
+        }
+    }
+}
+
+/// Value of a field of a day.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Date(Date),
+    Text(String),
+    Kwh(Energy),
+    Count(usize),
+}
+
+impl Value {
+    /// Compares values of the same field, hence of the same kind.
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            (Value::Kwh(a), Value::Kwh(b)) => Some(a.cmp(b)),
+            (Value::Count(a), Value::Count(b)) => Some(a.cmp(b)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Date(d) => write!(f, "{d}"),
+            Value::Text(s) => write!(f, "{s}"),
+            Value::Kwh(energy) => write!(f, "{energy}"),
+            Value::Count(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// Consumption of a day.
+#[derive(Debug, Clone)]
+pub struct Day {
+    pub date: Date,
+    pub day_type: DayType,
+    /// Energy of each period, indexed by period.
+    pub energy: [Energy; 3],
+    /// Energy since the first day, including this one.
+    pub cumulative: Energy,
+    pub readings: usize,
+    pub estimated: usize,
+}
+
+impl Day {
+    /// Energy of the whole day.
+    pub fn total(&self) -> Energy {
+        self.energy
+            .iter()
+            .fold(Energy(0), |sum, &e| sum.saturating_add(e))
+    }
+
+    pub fn value(&self, field: Field) -> Value {
+        match field {
+            Field::Date => Value::Date(self.date),
+            Field::DayType => Value::Text(self.day_type.name().to_string()),
+            Field::TotalKwh => Value::Kwh(self.total()),
+            Field::P1Kwh => Value::Kwh(self.energy[Period::P1.index()]),
+            Field::P2Kwh => Value::Kwh(self.energy[Period::P2.index()]),
+            Field::P3Kwh => Value::Kwh(self.energy[Period::P3.index()]),
+            Field::CumulativeKwh => Value::Kwh(self.cumulative),
+            Field::Readings => Value::Count(self.readings),
+            Field::Estimated => Value::Count(self.estimated),
+        }
+    }
+}
+
+/// Groups the readings by day.
+#[derive(Debug, Default)]
+pub struct Daily {
+    days: BTreeMap<Date, Day>,
+}
+
+impl Daily {
+    /// Adds a reading of `period` of a day of `day_type`; `counted` tells whether its kWh are
+    /// part of the consumption, which they aren't when estimated readings are excluded.
+    pub fn add(&mut self, r: &Reading, period: Period, day_type: DayType, counted: bool) {
+        let day = self.days.entry(r.date).or_insert(Day {
+            date: r.date,
+            day_type,
+            energy: [Energy(0); 3],
+            cumulative: Energy(0),
+            readings: 0,
+            estimated: 0,
+        });
+        day.readings += 1;
+        if r.estimated {
+            day.estimated += 1;
+        }
+        if counted {
+            let i = period.index();
+            day.energy[i] = day.energy[i].saturating_add(r.energy);
+        }
+    }
+
+    /// Returns the days in chronological order, with their cumulative consumption.
+    pub fn into_days(self) -> Vec<Day> {
+        let mut cumulative = Energy(0);
+        self.days
+            .into_values()
+            .map(|mut day| {
+                cumulative = cumulative.saturating_add(day.total());
+                day.cumulative = cumulative;
+                day
+            })
+            .collect()
+    }
+}
+
+/// Comparison operators of the filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+    /// Operators by their symbol; the two-character ones go first so that they are matched before
+    /// their prefixes.
+    const SYMBOLS: [(&'static str, Operator); 7] = [
+        ("==", Operator::Eq),
+        ("!=", Operator::Ne),
+        ("<=", Operator::Le),
+        (">=", Operator::Ge),
+        ("<", Operator::Lt),
+        (">", Operator::Gt),
+        ("=", Operator::Eq),
+    ];
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering.is_eq(),
+            Operator::Ne => ordering.is_ne(),
+            Operator::Lt => ordering.is_lt(),
+            Operator::Le => ordering.is_le(),
+            Operator::Gt => ordering.is_gt(),
+            Operator::Ge => ordering.is_ge(),
+        }
+    }
+}
+
+/// Condition that the days must meet to be in the report: a field compared with a value, e.g.
+/// `total_kwh > 15`.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    field: Field,
+    operator: Operator,
+    value: Value,
+}
+
+impl Filter {
+    /// Parses a `<field> <operator> <value>` condition, where the operator is one of `==` (or
+    /// `=`), `!=`, `<`, `<=`, `>` and `>=`, and the value is a `YYYY-MM-DD` date for the date
+    /// field, a day type name for the day type field, a whole number for the counts and a kWh
+    /// amount separated by `.` for the rest.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (pos, symbol, operator) = s
+            .char_indices()
+            .find_map(|(i, _)| {
+                Operator::SYMBOLS
+                    .iter()
+                    .find(|(symbol, _)| s[i..].starts_with(symbol))
+                    .map(|&(symbol, op)| (i, symbol, op))
+            })
+            .ok_or_else(|| format!("'{s}' doesn't have the format <field> <operator> <value>"))?;
+
+        let name = s[..pos].trim();
+        let field = Field::parse(name).ok_or_else(|| format!("'{name}' isn't a field"))?;
+        let literal = s[pos + symbol.len()..].trim();
+        let value = match field {
+            Field::Date => Date::parse_iso(literal).map(Value::Date),
+            Field::DayType => DayType::ALL
+                .into_iter()
+                .find(|t| t.name().eq_ignore_ascii_case(literal))
+                .map(|t| Value::Text(t.name().to_string())),
+            Field::Readings | Field::Estimated => literal.parse().ok().map(Value::Count),
+            _ => Energy::parse_kwh(literal).map(Value::Kwh),
+        }
+        .ok_or_else(|| format!("'{literal}' isn't a valid value for {name}"))?;
+
+        Ok(Filter {
+            field,
+            operator,
+            value,
+        })
+    }
+
+    pub fn matches(&self, day: &Day) -> bool {
+        day.value(self.field)
+            .compare(&self.value)
+            .is_some_and(|o| self.operator.holds(o))
+    }
+}
+
+/// Field to sort the report by, ascending or descending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: Field,
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Parses a field name, prefixed with `-` to sort descending.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (name, descending) = match s.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (s.strip_prefix('+').unwrap_or(s), false),
+        };
+        let field = Field::parse(name).ok_or_else(|| format!("'{name}' isn't a field"))?;
+
+        Ok(SortKey { field, descending })
+    }
+}
+
+/// Returns the days that match all the `filters`, sorted by `keys` in order of precedence and
+/// chronologically when they are equal.
+pub fn query<'a>(
+    days: impl IntoIterator<Item = &'a Day>,
+    filters: &[Filter],
+    keys: &[SortKey],
+) -> Vec<&'a Day> {
+    let mut days: Vec<&Day> = days
+        .into_iter()
+        .filter(|d| filters.iter().all(|f| f.matches(d)))
+        .collect();
+    // The sort is stable, so equal days stay in chronological order.
+    days.sort_by(|a, b| {
+        keys.iter()
+            .map(|k| {
+                let o = a
+                    .value(k.field)
+                    .compare(&b.value(k.field))
+                    .unwrap_or(Ordering::Equal);
+                if k.descending {
+                    o.reverse()
+                } else {
+                    o
+                }
+            })
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+
+    days
+}
+
+/// Writes `days` as CSV with a header row and a column for each of `fields`.
+pub fn write_csv<'a>(
+    out: &mut impl Write,
+    fields: &[Field],
+    days: impl IntoIterator<Item = &'a Day>,
+) -> io::Result<()> {
+    let header: Vec<&str> = fields.iter().map(|f| f.name()).collect();
+    writeln!(out, "{}", header.join(","))?;
+    for day in days {
+        let values: Vec<String> = fields.iter().map(|&f| day.value(f).to_string()).collect();
+        writeln!(out, "{}", values.join(","))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u8, day_type: DayType, kwh: [f64; 3], estimated: usize) -> Day {
+        Day {
+            date: Date::new(2023, 1, d).unwrap(),
+            day_type,
+            energy: kwh.map(|kwh| Energy::from_kwh(kwh).unwrap()),
+            cumulative: Energy(0),
+            readings: 24,
+            estimated,
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let days = [
+            day(2, DayType::Workday, [1.0, 2.5, 3.0], 0),
+            day(7, DayType::Weekend, [0.0, 0.0, 4.25], 2),
+        ];
+        let fields = [
+            Field::Date,
+            Field::DayType,
+            Field::TotalKwh,
+            Field::P2Kwh,
+            Field::Estimated,
+        ];
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &fields, &days).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,day_type,total_kwh,p2_kwh,estimated\n\
+             2023-01-02,Workday,6.500,2.500,0\n\
+             2023-01-07,Weekend,4.250,0.000,2\n"
+        );
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &[Field::Date], []).unwrap();
+        assert_eq!(out, b"date\n");
+    }
+
+    #[test]
+    fn daily() {
+        let reading = |d, kwh, estimated| Reading {
+            date: Date::new(2023, 1, d).unwrap(),
+            minute: 0,
+            minutes: 60,
+            energy: Energy::from_kwh(kwh).unwrap(),
+            estimated,
+            repeated: false,
+        };
+        let mut daily = Daily::default();
+        daily.add(&reading(3, 2.0, false), Period::P1, DayType::Workday, true);
+        daily.add(&reading(2, 1.0, false), Period::P3, DayType::Workday, true);
+        daily.add(&reading(3, 5.0, true), Period::P2, DayType::Workday, false);
+        daily.add(&reading(3, 0.5, false), Period::P1, DayType::Workday, true);
+
+        let days = daily.into_days();
+        let dates: Vec<String> = days.iter().map(|d| d.date.to_string()).collect();
+        assert_eq!(dates, ["2023-01-02", "2023-01-03"]);
+        assert_eq!(days[1].energy, [Energy(2_500_000), Energy(0), Energy(0)]);
+        assert_eq!((days[1].readings, days[1].estimated), (3, 1));
+        assert_eq!(days[0].cumulative, Energy(1_000_000));
+        assert_eq!(days[1].cumulative, Energy(3_500_000));
+    }
+
+    #[test]
+    fn filters() {
+        let filter = Filter::parse("total_kwh>=6.5").unwrap();
+        assert_eq!(
+            (filter.field, filter.operator, filter.value),
+            (Field::TotalKwh, Operator::Ge, Value::Kwh(Energy(6_500_000)))
+        );
+        let filter = Filter::parse(" date = 2023-01-02 ").unwrap();
+        assert_eq!(
+            (filter.field, filter.operator, filter.value),
+            (
+                Field::Date,
+                Operator::Eq,
+                Value::Date(Date::new(2023, 1, 2).unwrap())
+            )
+        );
+        let filter = Filter::parse("day_type != bank HOLIDAY").unwrap();
+        assert_eq!(
+            (filter.field, filter.operator, filter.value),
+            (
+                Field::DayType,
+                Operator::Ne,
+                Value::Text("Bank holiday".to_string())
+            )
+        );
+        assert_eq!(Filter::parse("readings<24").unwrap().operator, Operator::Lt);
+        assert_eq!(
+            Filter::parse("readings > 2").unwrap().operator,
+            Operator::Gt
+        );
+        assert_eq!(
+            Filter::parse("readings <= 2").unwrap().operator,
+            Operator::Le
+        );
+        assert_eq!(
+            Filter::parse("readings == 2").unwrap().operator,
+            Operator::Eq
+        );
+
+        for (s, error) in [
+            (
+                "total_kwh",
+                "'total_kwh' doesn't have the format <field> <operator> <value>",
+            ),
+            ("kwh > 1", "'kwh' isn't a field"),
+            (
+                "total_kwh > many",
+                "'many' isn't a valid value for total_kwh",
+            ),
+            (
+                "date < 02/01/2023",
+                "'02/01/2023' isn't a valid value for date",
+            ),
+            (
+                "day_type == Holiday",
+                "'Holiday' isn't a valid value for day_type",
+            ),
+            ("readings > 2.5", "'2.5' isn't a valid value for readings"),
+            ("total_kwh > -1", "'-1' isn't a valid value for total_kwh"),
+        ] {
+            assert_eq!(Filter::parse(s).unwrap_err(), error, "{s}");
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let workday = day(2, DayType::Workday, [1.0, 2.5, 3.0], 0);
+        let matches = |s| Filter::parse(s).unwrap().matches(&workday);
+
+        assert!(matches("total_kwh > 6"));
+        assert!(!matches("total_kwh > 6.5"));
+        assert!(matches("p2_kwh = 2.5"));
+        assert!(matches("readings >= 24"));
+        assert!(matches("date < 2023-01-03"));
+        assert!(matches("day_type == workday"));
+        assert!(!matches("day_type != Workday"));
+    }
+
+    #[test]
+    fn sorting() {
+        let days = [
+            day(2, DayType::Workday, [1.0, 0.0, 0.0], 1),
+            day(3, DayType::Workday, [2.0, 0.0, 0.0], 0),
+            day(4, DayType::Workday, [1.0, 0.0, 0.0], 0),
+            day(7, DayType::Weekend, [0.0, 0.0, 3.0], 1),
+            day(8, DayType::Weekend, [0.0, 0.0, 1.0], 0),
+        ];
+        let query = |filters: &[&str], keys: &[&str]| -> Vec<String> {
+            let filters: Vec<Filter> = filters.iter().map(|f| Filter::parse(f).unwrap()).collect();
+            let keys: Vec<SortKey> = keys.iter().map(|k| SortKey::parse(k).unwrap()).collect();
+            query(&days, &filters, &keys)
+                .into_iter()
+                .map(|d| d.date.to_string()[8..].to_string())
+                .collect()
+        };
+
+        assert_eq!(query(&[], &[]), ["02", "03", "04", "07", "08"]);
+        assert_eq!(query(&[], &["-total_kwh"]), ["07", "03", "02", "04", "08"]);
+        // Ties keep the chronological order.
+        assert_eq!(query(&[], &["total_kwh"]), ["02", "04", "08", "03", "07"]);
+        assert_eq!(
+            query(&[], &["day_type", "-total_kwh"]),
+            ["07", "08", "03", "02", "04"]
+        );
+        assert_eq!(
+            query(&[], &["+total_kwh", "estimated"]),
+            ["04", "08", "02", "03", "07"]
+        );
+        assert_eq!(
+            query(&["total_kwh < 3", "estimated == 0"], &["-date"]),
+            ["08", "04", "03"]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Conversion of UTC instants to the local time of peninsular Spain (Europe/Madrid), which is
+//! the time of the readings.
+//!
+//! It follows the EU summer time rule in force since 1996: CEST (UTC+2) from the last Sunday of
+//! March to the last Sunday of October, changing at 01:00 UTC, and CET (UTC+1) the rest of the
+//! year.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::date::Date;
+use crate::period::DAY_MINUTES;
+
+/// Minutes of a day as `i64`, the type of the instants.
+const DAY: i64 = DAY_MINUTES as i64;
+
+/// Returns the instant, in minutes since 1970-01-01 00:00 UTC, of `minute` of the day of `date`
+/// in UTC.
+pub fn utc_instant(date: Date, minute: u16) -> i64 {
+    date.days_since_epoch() * DAY + i64::from(minute)
+}
+
+/// Returns the offset from UTC, in minutes, of the local time at the `utc` instant.
+pub fn utc_offset(utc: i64) -> i64 {
+    let year = Date::from_days_since_epoch(utc.div_euclid(DAY)).year();
+    let (start, end) = summer_time(year);
+    if (start..end).contains(&utc) {
+        120
+    } else {
+        60
+    }
+}
+
+/// Returns the local date and minute of the day of the `utc` instant.
+pub fn to_local(utc: i64) -> (Date, u16) {
+    let local = utc + utc_offset(utc);
+    (
+        Date::from_days_since_epoch(local.div_euclid(DAY)),
+        local.rem_euclid(DAY) as u16,
+    )
+}
+
+/// Clock change of the day when the summer time starts or ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockChange {
+    /// The clocks go forward from 02:00 to 03:00, so the day has 23 hours.
+    Forward,
+    /// The clocks go back from 03:00 to 02:00, so the day has 25 hours.
+    Back,
+}
+
+/// Returns the clock change of `date`, if any.
+pub fn clock_change(date: Date) -> Option<ClockChange> {
+    let (start, end) = summer_time(date.year());
+    let day = date.days_since_epoch();
+    if day == start.div_euclid(DAY) {
+        Some(ClockChange::Forward)
+    } else if day == end.div_euclid(DAY) {
+        Some(ClockChange::Back)
+    } else {
+        None
+    }
+}
+
+/// Returns whether the `utc` instant is in the hour that the clocks repeat when they go back.
+pub fn is_repeated(utc: i64) -> bool {
+    let year = Date::from_days_since_epoch(utc.div_euclid(DAY)).year();
+    let (_, end) = summer_time(year);
+    (end..end + 60).contains(&utc)
+}
+
+/// Returns the UTC instant of `minute` of the local day of `date`; `repeated` picks the second
+/// occurrence of the times that the clocks repeat when they go back.
+pub fn local_to_utc(date: Date, minute: u16, repeated: bool) -> i64 {
+    let local = utc_instant(date, minute);
+    let (summer, winter) = (local - 120, local - 60);
+    match (utc_offset(summer) == 120, utc_offset(winter) == 60) {
+        (true, true) if !repeated => summer,
+        (true, false) => summer,
+        // The times skipped when the clocks go forward don't exist; they are taken as winter
+        // time.
+        _ => winter,
+    }
+}
+
+/// Returns the current local date and minute of the day.
+pub fn now() -> (Date, u16) {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("the system clock is after 1970");
+    to_local((since_epoch.as_secs() / 60) as i64)
+}
+
+/// Returns the UTC instants when the summer time of `year` starts and ends.
+pub fn summer_time(year: i32) -> (i64, i64) {
+    let change = |month| {
+        let last = Date::new(year, month, 31).expect("March and October have 31 days");
+        // `weekday` goes from 1 (Monday) to 7 (Sunday).
+        let days_after_sunday = i64::from(last.weekday() % 7);
+        (last.days_since_epoch() - days_after_sunday) * DAY + 60
+    };
+
+    (change(3), change(10))
+}
+
+/// Parses the `YYYY-MM-DDTHH:MM[:SS]` beginning of a timestamp, where a space can replace the `T`
+/// and the seconds can have a fraction, into the date, the minute of the day and the rest of `s`;
+/// the seconds are ignored.
+pub fn parse_date_time(s: &str) -> Option<(Date, u16, &str)> {
+    let (date, time) = s.split_once(['T', ' '])?;
+    let date = Date::parse_iso(date)?;
+    if time.as_bytes().get(2) != Some(&b':') {
+        return None;
+    }
+    let h: u16 = time.get(..2)?.parse().ok()?;
+    let m: u16 = time.get(3..5)?.parse().ok()?;
+    if h >= 24 || m >= 60 {
+        return None;
+    }
+
+    let rest = &time[5..];
+    let rest = match rest.strip_prefix(':') {
+        Some(s) => s.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.'),
+        None => rest,
+    };
+
+    Some((date, h * 60 + m, rest))
+}
+
+/// Parses an RFC 3339 timestamp, e.g. `2023-01-01T00:00:00.000Z` or `2023-01-01 01:00+01:00`,
+/// into its UTC instant; the seconds are ignored.
+pub fn parse_rfc3339(s: &str) -> Option<i64> {
+    let (date, minute, rest) = parse_date_time(s)?;
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let (oh, om) = rest[1..].split_once(':').unwrap_or((&rest[1..], "00"));
+            let (oh, om): (i64, i64) = (oh.parse().ok()?, om.parse().ok()?);
+            sign * (oh * 60 + om)
+        }
+    };
+
+    Some(utc_instant(date, minute) - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_date_time_rest() {
+        let date = Date::new(2023, 1, 2).unwrap();
+
+        assert_eq!(parse_date_time("2023-01-02T01:30"), Some((date, 90, "")));
+        assert_eq!(
+            parse_date_time("2023-01-02 01:30:15.5Z"),
+            Some((date, 90, "Z"))
+        );
+        assert_eq!(
+            parse_date_time("2023-01-02T01:30+01:00"),
+            Some((date, 90, "+01:00"))
+        );
+        assert_eq!(parse_date_time("2023-01-02T24:00"), None);
+        assert_eq!(parse_date_time("2023-01-02T1:30"), None);
+    }
+
+    #[test]
+    fn parse_rfc3339_offsets() {
+        let local = utc_instant(Date::new(2023, 1, 2).unwrap(), 90);
+
+        assert_eq!(parse_rfc3339("2023-01-02T01:30:00Z"), Some(local));
+        assert_eq!(parse_rfc3339("2023-01-02T01:30+01:00"), Some(local - 60));
+        assert_eq!(parse_rfc3339("2023-01-02T01:30"), None);
+    }
+
+    #[test]
+    fn summer_time_changes() {
+        let march = Date::new(2023, 3, 26).unwrap();
+        let october = Date::new(2023, 10, 29).unwrap();
+        assert_eq!(
+            summer_time(2023),
+            (utc_instant(march, 60), utc_instant(october, 60))
+        );
+        // 2024-03-31 and 2024-10-27 are the last Sundays of their months.
+        assert_eq!(
+            summer_time(2024),
+            (
+                utc_instant(Date::new(2024, 3, 31).unwrap(), 60),
+                utc_instant(Date::new(2024, 10, 27).unwrap(), 60)
+            )
+        );
+
+        assert_eq!(clock_change(march), Some(ClockChange::Forward));
+        assert_eq!(clock_change(october), Some(ClockChange::Back));
+        assert_eq!(clock_change(Date::new(2023, 3, 25).unwrap()), None);
+        assert_eq!(clock_change(Date::new(2023, 10, 22).unwrap()), None);
+
+        let (start, end) = summer_time(2023);
+        assert_eq!(utc_offset(start - 1), 60);
+        assert_eq!(utc_offset(start), 120);
+        assert_eq!(utc_offset(end - 1), 120);
+        assert_eq!(utc_offset(end), 60);
+        assert!(!is_repeated(end - 1));
+        assert!(is_repeated(end));
+        assert!(is_repeated(end + 59));
+        assert!(!is_repeated(end + 60));
+    }
+
+    #[test]
+    fn local_times_of_the_clock_changes() {
+        let march = Date::new(2023, 3, 26).unwrap();
+        let (start, _) = summer_time(2023);
+        assert_eq!(local_to_utc(march, 60, false), start - 60);
+        assert_eq!(local_to_utc(march, 180, false), start);
+        // 02:30 doesn't exist, and it's taken as winter time.
+        assert_eq!(local_to_utc(march, 150, false), start + 30);
+        assert_eq!(to_local(start), (march, 180));
+
+        let october = Date::new(2023, 10, 29).unwrap();
+        let (_, end) = summer_time(2023);
+        assert_eq!(local_to_utc(october, 120, false), end - 60);
+        assert_eq!(local_to_utc(october, 120, true), end);
+        assert_eq!(local_to_utc(october, 150, true), end + 30);
+        assert_eq!(local_to_utc(october, 180, false), end + 60);
+        assert_eq!(local_to_utc(october, 180, true), end + 60);
+        assert_eq!(to_local(end - 60), (october, 120));
+        assert_eq!(to_local(end), (october, 120));
+    }
+}
@@ -0,0 +1,363 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::date::Date;
+use crate::energy::Energy;
+use crate::period::{Period, Window};
+use crate::reader::ColumnMap;
+use crate::report::Field;
+
+/// Parses the meter counters of the 3 periods with the format `p1=N,p2=N,p3=N`; the counters are
+/// kWh whose decimals are separated by `.`.
+///
+/// All the periods must be present and they can be in any order.
+pub fn parse_meter_counter(s: &str) -> Result<[Energy; 3], String> {
+    let mut counters = [None; 3];
+    for pair in s.split(',') {
+        let (period, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("'{pair}' doesn't have the format <period>=<counter>"))?;
+        let period =
+            Period::parse(period).ok_or_else(|| format!("'{period}' isn't a valid period"))?;
+        let value = Energy::parse_kwh(value)
+            .ok_or_else(|| format!("'{value}' isn't a valid counter for {period}"))?;
+
+        if counters[period.index()].replace(value).is_some() {
+            return Err(format!("{period} counter is specified more than once"));
+        }
+    }
+
+    let mut result = [Energy(0); 3];
+    for p in Period::ALL {
+        result[p.index()] = counters[p.index()].ok_or_else(|| format!("{p} counter is missing"))?;
+    }
+
+    Ok(result)
+}
+
+/// Parses a column map with the format `timestamp=COLUMN,value=COLUMN,format=PATTERN`, with an
+/// optional `method=COLUMN`. The pairs are separated by `,`, so neither the columns nor the
+/// pattern can contain it.
+///
+/// The pattern must have the date (`%d`, `%m` and `%Y`) and the hour (`%H`); besides them, it can
+/// only have the minute (`%M`) and `%%`.
+pub fn parse_csv_map(s: &str) -> Result<ColumnMap, String> {
+    let (mut timestamp, mut value, mut method, mut format) = (None, None, None, None);
+    for pair in s.split(',') {
+        let (key, v) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("'{pair}' doesn't have the format <key>=<value>"))?;
+        let entry = match key {
+            "timestamp" => &mut timestamp,
+            "value" => &mut value,
+            "method" => &mut method,
+            "format" => &mut format,
+            _ => return Err(format!("'{key}' isn't timestamp, value, method nor format")),
+        };
+        if v.is_empty() {
+            return Err(format!("{key} is empty"));
+        }
+        if entry.replace(v.to_string()).is_some() {
+            return Err(format!("{key} is specified more than once"));
+        }
+    }
+
+    let format = format.ok_or("format is missing")?;
+    let mut specifiers = Vec::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            match chars.next() {
+                Some('%') => {}
+                Some(spec @ ('d' | 'm' | 'Y' | 'H' | 'M')) => specifiers.push(spec),
+                Some(spec) => return Err(format!("format has the unsupported specifier %{spec}")),
+                None => return Err("format ends with a lone %".into()),
+            }
+        }
+    }
+    for spec in ['d', 'm', 'Y', 'H'] {
+        if !specifiers.contains(&spec) {
+            return Err(format!("format doesn't have %{spec}"));
+        }
+    }
+
+    Ok(ColumnMap {
+        timestamp: timestamp.ok_or("timestamp is missing")?,
+        value: value.ok_or("value is missing")?,
+        method,
+        format,
+    })
+}
+
+/// Parses a comma separated list of report fields, e.g. `date,total_kwh,p1_kwh`.
+pub fn parse_fields(s: &str) -> Result<Vec<Field>, String> {
+    s.split(',')
+        .map(|name| {
+            Field::parse(name).ok_or_else(|| {
+                let names: Vec<&str> = Field::ALL.iter().map(|f| f.name()).collect();
+                format!("'{name}' isn't any of {}", names.join(", "))
+            })
+        })
+        .collect()
+}
+
+/// Parses a comma separated list of windows whose limits are hours (e.g. `8-10,14-18,22-0`) or
+/// times with `H:MM` format (e.g. `8:30-10`).
+pub fn parse_windows(s: &str) -> Result<Vec<Window>, String> {
+    s.split(',')
+        .map(|w| {
+            let (start, end) = w
+                .split_once('-')
+                .ok_or_else(|| format!("'{w}' doesn't have the format <start>-<end>"))?;
+            let start = parse_time(start)?;
+            // 24 is accepted as the end of the day as well as 0.
+            let end = match end {
+                "24" | "24:00" => 0,
+                _ => parse_time(end)?,
+            };
+            if start == end {
+                return Err(format!("'{w}' is an empty window"));
+            }
+
+            Ok(Window { start, end })
+        })
+        .collect()
+}
+
+/// Parses a time of the day (`H` or `H:MM`) into the minute of the day.
+fn parse_time(s: &str) -> Result<u16, String> {
+    let (hour, minute) = s.split_once(':').unwrap_or((s, "00"));
+    match (hour.parse::<u16>(), minute.parse::<u16>()) {
+        (Ok(h), Ok(m)) if minute.len() == 2 && h < 24 && m < 60 => Ok(h * 60 + m),
+        _ => Err(format!("'{s}' isn't a valid time of the day (H or H:MM)")),
+    }
+}
+
+/// Parses a comma separated list of dates with `YYYY-MM-DD` format.
+pub fn parse_dates(s: &str) -> Result<Vec<Date>, String> {
+    s.split(',')
+        .map(|d| Date::parse_iso(d).ok_or_else(|| format!("'{d}' isn't a valid YYYY-MM-DD date")))
+        .collect()
+}
+
+/// Expands the `*` and `?` wildcards of the file name of `pattern` into the matching files,
+/// sorted by name; a `pattern` without wildcards is returned as it is.
+///
+/// Wildcards in the directory components aren't supported.
+pub fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let path = Path::new(pattern);
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    if !name.contains(['*', '?']) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let entries = fs::read_dir(dir).map_err(|e| format!("cannot read {}: {e}", dir.display()))?;
+
+    let name: Vec<char> = name.chars().collect();
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("cannot read {}: {e}", dir.display()))?;
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+
+        let file_name: Vec<char> = file_name.chars().collect();
+        if wildcard_match(&name, &file_name) && entry.path().is_file() {
+            paths.push(entry.path());
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(format!("'{pattern}' doesn't match any file"));
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+fn wildcard_match(pattern: &[char], s: &[char]) -> bool {
+    match (pattern.first(), s.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            wildcard_match(&pattern[1..], s) || (!s.is_empty() && wildcard_match(pattern, &s[1..]))
+        }
+        (Some('?'), Some(_)) => wildcard_match(&pattern[1..], &s[1..]),
+        (Some(p), Some(c)) if p == c => wildcard_match(&pattern[1..], &s[1..]),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meter_counters() {
+        assert_eq!(
+            parse_meter_counter("p3=3,P1=1.5,p2=2.25"),
+            Ok([Energy(1_500_000), Energy(2_250_000), Energy(3_000_000)])
+        );
+
+        let cases = [
+            ("p1=1,p2=2", "P3 counter is missing"),
+            ("p1=1,p1=2,p3=3", "P1 counter is specified more than once"),
+            ("p1=1,p2=2,p4=3", "'p4' isn't a valid period"),
+            ("p1=1,p2=-2,p3=3", "'-2' isn't a valid counter for P2"),
+            ("p1=1,p2=2.5.5,p3=3", "'2.5.5' isn't a valid counter for P2"),
+            (
+                "p1=1,p2,p3=3",
+                "'p2' doesn't have the format <period>=<counter>",
+            ),
+        ];
+        for (s, error) in cases {
+            assert_eq!(parse_meter_counter(s), Err(error.to_string()), "{s}");
+        }
+    }
+
+    #[test]
+    fn windows() {
+        assert_eq!(
+            parse_windows("8-10:30,22-24,23:15-0"),
+            Ok(vec![
+                Window {
+                    start: 480,
+                    end: 630
+                },
+                Window {
+                    start: 1320,
+                    end: 0
+                },
+                Window {
+                    start: 1395,
+                    end: 0
+                },
+            ])
+        );
+        assert_eq!(
+            parse_windows("8-8"),
+            Err("'8-8' is an empty window".to_string())
+        );
+        assert_eq!(
+            parse_windows("8-25"),
+            Err("'25' isn't a valid time of the day (H or H:MM)".to_string())
+        );
+        assert_eq!(
+            parse_windows("8:5-9"),
+            Err("'8:5' isn't a valid time of the day (H or H:MM)".to_string())
+        );
+        assert_eq!(
+            parse_windows("8"),
+            Err("'8' doesn't have the format <start>-<end>".to_string())
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            parse_dates("2023-01-06,2023-12-25"),
+            Ok(vec![
+                Date::new(2023, 1, 6).unwrap(),
+                Date::new(2023, 12, 25).unwrap()
+            ])
+        );
+        assert_eq!(
+            parse_dates("2023-01-06,06/01/2023"),
+            Err("'06/01/2023' isn't a valid YYYY-MM-DD date".to_string())
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        let matches = |pattern: &str, s: &str| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let s: Vec<char> = s.chars().collect();
+            wildcard_match(&pattern, &s)
+        };
+
+        assert!(matches("*.csv", "2023.csv"));
+        assert!(matches("*.csv", ".csv"));
+        assert!(matches("202?-*.csv", "2023-01.csv"));
+        assert!(matches("2023.csv", "2023.csv"));
+        assert!(!matches("*.csv", "2023.csv.gz"));
+        assert!(!matches("202?.csv", "20234.csv"));
+        assert!(!matches("?2023.csv", "2023.csv"));
+    }
+
+    #[test]
+    fn globs() {
+        assert_eq!(expand_glob("2023.csv"), Ok(vec![PathBuf::from("2023.csv")]));
+        assert_eq!(
+            expand_glob("/nonexistent-dir/*.csv"),
+            Err("cannot read /nonexistent-dir: No such file or directory (os error 2)".to_string())
+        );
+        assert_eq!(
+            expand_glob("src/*.nothing"),
+            Err("'src/*.nothing' doesn't match any file".to_string())
+        );
+        assert_eq!(
+            expand_glob("src/e*r.rs"),
+            Ok(vec![PathBuf::from("src/error.rs")])
+        );
+    }
+
+    #[test]
+    fn csv_map() {
+        assert_eq!(
+            parse_csv_map("timestamp=Fecha y hora,value=kWh,method=Tipo,format=%d/%m/%Y %H:%M"),
+            Ok(ColumnMap {
+                timestamp: "Fecha y hora".into(),
+                value: "kWh".into(),
+                method: Some("Tipo".into()),
+                format: "%d/%m/%Y %H:%M".into(),
+            })
+        );
+        assert_eq!(
+            parse_csv_map("format=%Y%m%d%H,value=v,timestamp=t").map(|m| m.method),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn invalid_csv_maps() {
+        let cases = [
+            ("timestamp=t,value=v", "format is missing"),
+            ("value=v,format=%d/%m/%Y %H", "timestamp is missing"),
+            (
+                "timestamp=t,value=v,format=%d/%m/%Y",
+                "format doesn't have %H",
+            ),
+            (
+                "timestamp=t,value=v,format=%d/%m/%y %H",
+                "format has the unsupported specifier %y",
+            ),
+            (
+                "timestamp=t,value=v,format=%d/%m/%Y %H%",
+                "format ends with a lone %",
+            ),
+            ("timestamp=t,value=,format=%d/%m/%Y %H", "value is empty"),
+            (
+                "timestamp=t,timestamp=u",
+                "timestamp is specified more than once",
+            ),
+            (
+                "column=t",
+                "'column' isn't timestamp, value, method nor format",
+            ),
+            // A pattern with `,` is split into another pair.
+            (
+                "timestamp=t,value=v,format=%d/%m/%Y,%H",
+                "'%H' doesn't have the format <key>=<value>",
+            ),
+        ];
+        for (map, error) in cases {
+            assert_eq!(parse_csv_map(map), Err(error.to_string()), "{map}");
+        }
+    }
+}